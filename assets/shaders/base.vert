@@ -4,8 +4,8 @@ layout (location = 1) in vec2 uv;
 layout (location = 2) in vec3 normal;
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+
+#include "camera.glsl"
 
 out vec3 v_pos;
 out vec2 v_uv;
@@ -0,0 +1,4 @@
+{
+  "vert": "light.vert",
+  "frag": "blit.frag"
+}
@@ -5,7 +5,6 @@ in vec3 v_normal;
 
 uniform vec3 color;
 uniform sampler2D tex;
-uniform bool use_tex;
 uniform float spec;
 uniform float metallic;
 
@@ -16,9 +15,9 @@ layout(location = 3) out vec4 gmaterial;
 
 void main() {
 	galbedo = vec4(color, 1.0);
-	if (use_tex) {
-		galbedo *= texture(tex, v_uv);
-	}
+#ifdef USE_TEX
+	galbedo *= texture(tex, v_uv);
+#endif
 	gposition = vec4(v_pos, 1.0);
 	gnormal = vec4(v_normal, 1.0);
 	gmaterial = vec4(spec, metallic, 0.0, 0.0);
@@ -0,0 +1,4 @@
+{
+  "vert": "base.vert",
+  "frag": "default.frag"
+}
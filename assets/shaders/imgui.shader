@@ -0,0 +1,4 @@
+{
+  "vert": "imgui.vert",
+  "frag": "imgui.frag"
+}
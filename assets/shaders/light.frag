@@ -7,21 +7,16 @@ uniform sampler2D gnormal;
 uniform sampler2D gmaterial;
 uniform sampler2D ssao_tex;
 uniform sampler2D shadow_map;
-uniform mat4 view;
-uniform mat4 projection;
-uniform vec3 cam_pos;
+#include "camera.glsl"
+#include "lights.glsl"
 uniform vec3 sun_dir;
 uniform mat4 sun_view;
 uniform mat4 sun_projection;
-uniform int tonemap;
+// 0 aces, 1 filmic, 2 reinhard, 3 uncharted2
+#ifndef TONEMAP
+#define TONEMAP 0
+#endif
 
-struct light_t {
-	vec3 pos;
-	vec3 color;
-	float strength;
-};
-uniform light_t lights[100];
-uniform int num_lights;
 
 out vec4 f_color;
 
@@ -125,26 +120,21 @@ void main(){
 		float reflection_multiplier = clamp(pow(metallic, 3) * -reflected.z, 0.0, 0.9);
 		color = light + texture(galbedo, coords).rgb * reflection_multiplier;
 	}
-	switch (tonemap) {
-		case 0: // aces
-			const float a = 2.51;
-			const float b = 0.03;
-			const float c = 2.43;
-			const float d = 0.59;
-			const float e = 0.14;
-			color = clamp((color * (a * color + b)) / (color * (c * color + d) + e), 0.0, 1.0);
-			break;
-		case 1: // filmic
-			color = max(vec3(0.0), color - 0.004);
-			color = (color * (6.2 * color + 0.5)) / (color * (6.2 * color + 1.7) + 0.06);
-			color = pow(color, vec3(2.2));
-			break;
-		case 2: // reinhard
-			color = color / (1.0 + color);
-			break;
-		case 3: // uncharted2
-			color = uncharted2(color * 2.0) / uncharted2(vec3(11.2));
-			break;
-	}
+#if TONEMAP == 0 // aces
+	const float a = 2.51;
+	const float b = 0.03;
+	const float c = 2.43;
+	const float d = 0.59;
+	const float e = 0.14;
+	color = clamp((color * (a * color + b)) / (color * (c * color + d) + e), 0.0, 1.0);
+#elif TONEMAP == 1 // filmic
+	color = max(vec3(0.0), color - 0.004);
+	color = (color * (6.2 * color + 0.5)) / (color * (6.2 * color + 1.7) + 0.06);
+	color = pow(color, vec3(2.2));
+#elif TONEMAP == 2 // reinhard
+	color = color / (1.0 + color);
+#elif TONEMAP == 3 // uncharted2
+	color = uncharted2(color * 2.0) / uncharted2(vec3(11.2));
+#endif
 	f_color = vec4(color, 1.0);
 }
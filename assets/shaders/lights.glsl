@@ -0,0 +1,10 @@
+struct light_t {
+	vec3 pos;
+	float strength;
+	vec3 color;
+};
+
+layout (std140) uniform Lights {
+	int num_lights;
+	light_t lights[100];
+};
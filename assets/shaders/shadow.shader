@@ -0,0 +1,4 @@
+{
+  "vert": "shadow.vert",
+  "frag": "shadow.frag"
+}
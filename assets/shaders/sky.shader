@@ -0,0 +1,4 @@
+{
+  "vert": "sky.vert",
+  "frag": "sky.frag"
+}
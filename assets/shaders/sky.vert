@@ -2,8 +2,7 @@
 layout (location = 0) in vec3 pos;
 layout (location = 1) in vec2 uv;
 
-uniform mat4 view;
-uniform mat4 projection;
+#include "camera.glsl"
 
 out vec3 v_pos;
 
@@ -5,9 +5,10 @@ uniform sampler2D galbedo;
 uniform sampler2D gposition;
 uniform sampler2D gnormal;
 uniform sampler2D noise;
-uniform vec3 samples[64];
-uniform mat4 view;
-uniform mat4 projection;
+#include "camera.glsl"
+layout (std140) uniform Ssao {
+	vec3 samples[64];
+};
 
 float radius = 0.5;
 float bias = 0.025;
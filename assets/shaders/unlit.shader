@@ -0,0 +1,4 @@
+{
+  "vert": "base.vert",
+  "frag": "unlit.frag"
+}
@@ -5,11 +5,12 @@ use phosphor::log::LevelFilter;
 use phosphor::math::Vec3;
 use phosphor::assets::Assets;
 use phosphor::scene::Scene;
-use phosphor::gfx::{Renderer, Mesh, Query};
+use phosphor::gfx::{self, Renderer, Mesh, Query};
 use phosphor::glfw::{CursorMode, Key, MouseButton, Action};
 use phosphor_3d::{
-  Transform, Camera, Model, Material, Light, SkySettings, ScenePerf, Tonemap, scenerenderer_plugin,
+  Transform, Camera, Model, Material, Light, SkySettings, Tonemap, scenerenderer_plugin,
 };
+use phosphor_3d::render_graph::RenderGraph;
 use phosphor_imgui::imgui_plugin;
 use phosphor_imgui::imgui::{Ui, Condition, Drag};
 use phosphor_fmod::{AudioSource, fmod_plugin};
@@ -73,7 +74,7 @@ fn start(world: &mut World) -> Result {
     })
     .insert(AudioSource::new(assets.load("portal-radio.mp3")?))
     .insert(
-      ColliderBuilder::trimesh(&garf_mesh)
+      ColliderBuilder::trimesh(&garf_mesh.loaded()?)
         .attach_rb(garf_rb)
         .build(world),
     )
@@ -81,7 +82,7 @@ fn start(world: &mut World) -> Result {
   world
     .spawn("floor")
     .insert(Transform::new().scale(Vec3::new(10.0, 0.01, 10.0)))
-    .insert(Model::new(assets.load("cube.obj")?))
+    .insert(Model::new(assets.load("builtin:cube")?))
     .insert(Material {
       color: Vec3::splat(0.75),
       tex: None,
@@ -96,7 +97,7 @@ fn start(world: &mut World) -> Result {
         .pos(Vec3::new(2.0, 1.5, -2.0))
         .scale(Vec3::splat(0.1)),
     )
-    .insert(Model::new(assets.load("sphere.obj")?))
+    .insert(Model::new(assets.load("builtin:sphere")?))
     .insert(Light::new(Vec3::new(1.0, 0.0, 1.0)));
 
   Ok(())
@@ -172,9 +173,9 @@ fn ui(world: &mut World) -> Result {
             let assets = world.get_resource::<Assets>().unwrap();
             let mut rng = rand::thread_rng();
             let (mesh, collider) = match rng.gen_range(0..=2) {
-              0 => ("sphere.obj", ColliderBuilder::ball(0.5)),
-              1 => ("cube.obj", ColliderBuilder::cuboid(0.5, 0.5, 0.5)),
-              2 => ("cone.obj", ColliderBuilder::cone(0.5, 0.5)),
+              0 => ("builtin:sphere", ColliderBuilder::ball(0.5)),
+              1 => ("builtin:cube", ColliderBuilder::cuboid(0.5, 0.5, 0.5)),
+              2 => ("builtin:cone", ColliderBuilder::cone(0.5, 0.5)),
               _ => unreachable!(),
             };
             let rb = RigidBodyBuilder::dynamic().build(world);
@@ -209,11 +210,10 @@ fn ui(world: &mut World) -> Result {
           }
         }
         if let Some(_) = ui.tab_item("Graphics") {
-          let scene_perf = world.get_resource::<ScenePerf>().unwrap();
-          pass(ui, "shadow", &mut scene_perf.shadow_pass);
-          pass(ui, "geometry", &mut scene_perf.geometry_pass);
-          pass(ui, "ssao", &mut scene_perf.ssao_pass);
-          pass(ui, "lighting", &mut scene_perf.lighting_pass);
+          for p in world.get_resource::<RenderGraph>().unwrap().passes() {
+            pass(ui, p.name, &mut p.query);
+          }
+          ui.text(format!("gl objects: {}", gfx::live_objects()));
           let tonemap = world.get_resource::<Tonemap>().unwrap();
           if let Some(_) = ui.begin_combo("Tonemap", tonemap.name()) {
             for t in Tonemap::ALL {
@@ -0,0 +1,25 @@
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+#[doc(hidden)]
+pub use log as __log;
+
+static LOGGED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+// true the first time a message is seen
+#[doc(hidden)]
+pub fn __first(msg: &str) -> bool {
+  let mut logged = LOGGED.lock().unwrap();
+  !logged.contains(msg) && logged.insert(msg.to_string())
+}
+
+// warns once for each distinct message, eg. from systems that run every frame
+#[macro_export]
+macro_rules! warn_once {
+  ($($arg:tt)+) => {{
+    let msg = format!($($arg)+);
+    if $crate::__first(&msg) {
+      $crate::__log::warn!("{}", msg);
+    }
+  }};
+}
@@ -0,0 +1,192 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::fmt;
+use std::fs;
+use log::{info, warn};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::Error;
+use serde_json::{Map, Value};
+use crate::{Result, vfs};
+
+// persistent id of an asset, stored in its .meta so references survive renames and moves
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Guid(pub u128);
+
+impl Guid {
+  pub fn new() -> Self {
+    Self(rand::random())
+  }
+}
+
+impl fmt::Display for Guid {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:032x}", self.0)
+  }
+}
+
+// hex in text formats
+impl Serialize for Guid {
+  fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+      self.to_string().serialize(serializer)
+    } else {
+      self.0.serialize(serializer)
+    }
+  }
+}
+
+impl<'de> Deserialize<'de> for Guid {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+    if deserializer.is_human_readable() {
+      let s: String = Deserialize::deserialize(deserializer)?;
+      u128::from_str_radix(&s, 16)
+        .map(Self)
+        .map_err(D::Error::custom)
+    } else {
+      Ok(Self(Deserialize::deserialize(deserializer)?))
+    }
+  }
+}
+
+#[derive(Deserialize)]
+struct Meta {
+  guid: Option<Guid>,
+}
+
+// maps guids to the current path of each asset
+// read only unless `set_writable` is called, only the editor and phosphor-cook should write .metas
+pub struct AssetDb {
+  paths: HashMap<Guid, String>,
+  guids: HashMap<String, Guid>,
+  writable: bool,
+}
+
+impl AssetDb {
+  pub fn new() -> Self {
+    let mut db = Self {
+      paths: HashMap::new(),
+      guids: HashMap::new(),
+      writable: false,
+    };
+    if let Err(e) = db.scan() {
+      warn!("Couldnt scan assets. {}", e);
+    }
+    db
+  }
+
+  // lets `guid` write new .metas and rescans to move the ones left behind
+  pub fn set_writable(&mut self) -> Result {
+    self.writable = true;
+    self.scan()
+  }
+
+  // reads every .meta, those left behind by a renamed or moved asset are moved when writable
+  // and only followed otherwise
+  pub fn scan(&mut self) -> Result {
+    self.paths.clear();
+    self.guids.clear();
+    let files = vfs::files()?;
+    let names: HashSet<&str> = files.iter().map(String::as_str).collect();
+    let mut orphans = vec![];
+    for meta in files.iter().filter(|f| f.ends_with(".meta")) {
+      let Some(guid) = serde_json::from_slice::<Meta>(&vfs::read(meta)?)?.guid else {
+        continue;
+      };
+      let path = meta.strip_suffix(".meta").unwrap();
+      if names.contains(path) {
+        self.insert(guid, path.to_string());
+      } else {
+        orphans.push((guid, path));
+      }
+    }
+    let mut claimed = HashSet::new();
+    for (guid, old) in orphans {
+      // an asset with the same file name and no .meta of its own
+      let mut moved = files.iter().filter(|f| {
+        !f.ends_with(".meta")
+          && file_name(f) == file_name(old)
+          && !names.contains(format!("{}.meta", f).as_str())
+          && !claimed.contains(*f)
+      });
+      let (Some(new), None) = (moved.next(), moved.next()) else {
+        continue;
+      };
+      claimed.insert(new);
+      self.insert(guid, new.clone());
+      if !self.writable {
+        continue;
+      }
+      let (Some(from), Some(to)) = (vfs::dir_of(&format!("{}.meta", old)), vfs::dir_of(new)) else {
+        continue;
+      };
+      fs::rename(
+        from.join(format!("{}.meta", old)),
+        to.join(format!("{}.meta", new)),
+      )?;
+      info!("Moved '{}' to '{}'.", old, new);
+    }
+    Ok(())
+  }
+
+  pub fn path(&self, guid: Guid) -> Option<&str> {
+    self.paths.get(&guid).map(String::as_str)
+  }
+
+  pub fn find(&self, path: &str) -> Option<Guid> {
+    self.guids.get(path).copied()
+  }
+
+  // assigns a guid to loose files that dont have one yet, when writable
+  pub fn guid(&mut self, path: &str) -> Result<Guid> {
+    if let Some(guid) = self.find(path) {
+      return Ok(guid);
+    }
+    if !self.writable {
+      return Err(
+        format!(
+          "'{}' has no guid, import it with the editor or phosphor-cook.",
+          path
+        )
+        .into(),
+      );
+    }
+    let guid = Guid::new();
+    let mut meta = Map::new();
+    meta.insert("guid".to_string(), serde_json::to_value(guid)?);
+    write_meta(path, meta)?;
+    self.insert(guid, path.to_string());
+    Ok(guid)
+  }
+
+  fn insert(&mut self, guid: Guid, path: String) {
+    if let Some(other) = self.paths.get(&guid).filter(|p| **p != path) {
+      warn!("'{}' and '{}' have the same guid.", other, path);
+    }
+    self.guids.insert(path.clone(), guid);
+    self.paths.insert(guid, path);
+  }
+}
+
+// merges `fields` into the asset's .meta, keeping everything else
+pub(crate) fn write_meta(path: &str, fields: Map<String, Value>) -> Result {
+  let dir = vfs::dir_of(path).ok_or("Metadata can only be saved for loose files.")?;
+  let meta = dir.join(format!("{}.meta", path));
+  let mut map = match fs::read(&meta) {
+    Ok(data) => serde_json::from_slice(&data)?,
+    Err(_) => Map::new(),
+  };
+  map.extend(fields);
+  fs::write(meta, serde_json::to_string_pretty(&map)?)?;
+  Ok(())
+}
+
+// true for a .meta without import settings, eg. one just written to give its asset a guid
+pub(crate) fn only_guid(meta: &[u8]) -> bool {
+  serde_json::from_slice::<Map<String, Value>>(meta)
+    .ok()
+    .is_some_and(|m| m.keys().all(|k| k == "guid"))
+}
+
+fn file_name(path: &str) -> Option<&str> {
+  Path::new(path).file_name()?.to_str()
+}
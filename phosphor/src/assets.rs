@@ -1,95 +1,727 @@
-use std::collections::HashMap;
-use std::rc::Rc;
-use std::ops::Deref;
-use std::any::Any;
-use log::{error, trace};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender, Receiver};
+use std::sync::{Arc, Mutex};
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::rc::{Rc, Weak};
+use std::any::{Any, TypeId};
+use std::{fmt, mem, thread};
+use log::{error, warn, info, trace};
 use linkme::distributed_slice;
-use serde::{Serialize, Deserialize, Deserializer};
+use notify::{Watcher, RecommendedWatcher, RecursiveMode, Event, EventKind};
+use notify::event::ModifyKind;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::{Error, DeserializeOwned};
 use crate::ecs::World;
-use crate::{Result, TypeIdNamed, WORLD};
+use crate::asset_db::{self, AssetDb, Guid};
+use crate::{Result, TypeIdNamed, WORLD, vfs};
+
+pub type Decoded = Box<dyn Any + Send>;
+pub type DecodeFn = fn(&str) -> Result<Decoded>;
+pub type UploadFn = fn(&mut World, Decoded) -> Result<Rc<dyn Any>>;
+pub type CookFn = fn(&str) -> Result<Vec<u8>>;
+pub type UncookFn = fn(&[u8]) -> Result<Decoded>;
+
+// prefix of assets generated by their loader instead of read from a file, eg. "builtin:cube"
+pub const BUILTIN: &str = "builtin:";
 
 pub struct AssetLoader {
   pub id: TypeIdNamed,
   pub loader: fn(&mut World, &str) -> Result<Rc<dyn Any>>,
+  pub exts: &'static [&'static str],
+  // split loaders decode on a worker thread and upload on the main thread
+  pub decode: Option<DecodeFn>,
+  pub upload: Option<UploadFn>,
+  // serializes decoded data so it can be loaded without decoding the source again
+  pub cook: Option<CookFn>,
+  pub uncook: Option<UncookFn>,
+  pub memory: Option<fn(&dyn Any) -> AssetMemory>,
 }
 
 #[distributed_slice]
 pub static ASSET_LOADERS: [AssetLoader] = [..];
 
+pub enum AssetEvent {
+  Modified(TypeIdNamed, String),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LoadState {
+  Loading,
+  Loaded,
+  Failed,
+  Unloaded,
+}
+
+// bytes used by an asset
+#[derive(Clone, Copy, Default, Debug)]
+pub struct AssetMemory {
+  pub cpu: usize,
+  pub gpu: usize,
+}
+
+pub struct MemoryReport {
+  pub id: TypeIdNamed,
+  pub count: usize,
+  pub memory: AssetMemory,
+}
+
+type Job = (&'static AssetLoader, String);
+
+struct Workers {
+  jobs: Sender<Job>,
+  done: Receiver<(TypeIdNamed, String, Result<Decoded>)>,
+}
+
+struct AssetWatcher {
+  roots: Vec<PathBuf>,
+  rx: Receiver<notify::Result<Event>>,
+  _watcher: RecommendedWatcher,
+}
+
+// every loaded asset stays cached, so loading it again by path is free, until `unload` or `unload_unused`
+// eg. call unload_unused after switching levels to free what only the previous level used
 pub struct Assets {
   pub handles: HashMap<TypeIdNamed, Vec<Handle<dyn Any>>>,
+  pub db: AssetDb,
+  headless: bool,
+  watcher: Option<AssetWatcher>,
+  events: Vec<AssetEvent>,
+  workers: Option<Workers>,
+  // loaders without a decode step, loaded on the next update
+  queued: Vec<(TypeIdNamed, String)>,
+  reloads: HashSet<String>,
+  // files that other assets were built from, eg. shader includes
+  dependents: HashMap<String, HashSet<String>>,
 }
 
 impl Assets {
   pub fn new() -> Self {
+    let watcher = match AssetWatcher::new() {
+      Ok(w) => Some(w),
+      Err(e) => {
+        warn!("Couldnt watch assets, hot reloading is disabled. {}", e);
+        None
+      }
+    };
     Self {
       handles: HashMap::new(),
+      db: AssetDb::new(),
+      headless: false,
+      watcher,
+      events: vec![],
+      workers: None,
+      queued: vec![],
+      reloads: HashSet::new(),
+      dependents: HashMap::new(),
+    }
+  }
+
+  // only checks that files exist, handles never get data
+  pub fn headless() -> Self {
+    Self {
+      handles: HashMap::new(),
+      db: AssetDb::new(),
+      headless: true,
+      watcher: None,
+      events: vec![],
+      workers: None,
+      queued: vec![],
+      reloads: HashSet::new(),
+      dependents: HashMap::new(),
     }
   }
 
   pub fn load<T: Any>(&mut self, path: &str) -> Result<Handle<T>> {
-    let t = TypeIdNamed::of::<T>();
-    let loader = match ASSET_LOADERS.iter().find(|l| l.id == t) {
-      Some(s) => s,
-      None => {
-        error!("Unknown asset type '{}'.", t.name);
-        panic!();
+    let loader = find_loader(TypeIdNamed::of::<T>())?;
+    Ok(self.load_with(loader, path)?.cast())
+  }
+
+  // picks the loader from the file extension
+  pub fn load_untyped(&mut self, path: &str) -> Result<(TypeIdNamed, Handle<dyn Any>)> {
+    let loader = loader_for(path)?;
+    Ok((loader.id, self.load_with(loader, path)?))
+  }
+
+  fn load_with(&mut self, loader: &AssetLoader, path: &str) -> Result<Handle<dyn Any>> {
+    let t = loader.id;
+    if let Some(h) = self.find(t, path) {
+      return Ok(h.clone());
+    }
+    // sub assets like "scene.glb#mesh0" are added by the loader of their file
+    let file = path.split('#').next().unwrap();
+    if !self.headless && file != path {
+      self.load_untyped(file)?;
+      return match self.find(t, path) {
+        Some(h) => Ok(h.clone()),
+        None => Err(format!("Missing asset '{}'.", path).into()),
+      };
+    }
+    trace!("Loading '{}' from '{}'.", t.name, path);
+    let guid = self.import(file);
+    let h = if self.headless {
+      if !vfs::exists(file) && !file.starts_with(BUILTIN) {
+        return Err(format!("Missing asset '{}'.", path).into());
       }
+      Handle::new(path, t, None, LoadState::Loaded)
+    } else {
+      let data = load_data(loader, unsafe { WORLD.get_mut().unwrap() }, path)?;
+      Handle::new(path, t, Some(data), LoadState::Loaded)
     };
-    self.handles.entry(t).or_insert(vec![]);
-    let v = self.handles.get_mut(&t).unwrap();
-    Ok(match v.iter().find(|h| h.name == path) {
-      Some(h) => h.downcast(),
-      None => {
-        trace!("Loading '{}' from '{}'.", t.name, path);
-        let h = Handle {
-          name: path.to_string(),
-          data: (loader.loader)(
-            unsafe { WORLD.get_mut().unwrap() },
-            &format!("assets/{}", path),
-          )?,
-        };
-        v.push(h.clone());
-        h.downcast()
+    h.slot.guid.set(guid);
+    self.handles.entry(t).or_default().push(h.clone());
+    Ok(h)
+  }
+
+  // adds an asset that wasnt loaded from its own file, replacing any with the same name
+  pub fn add<T: Any>(&mut self, name: &str, asset: T) -> Handle<T> {
+    let t = TypeIdNamed::of::<T>();
+    if let Some(h) = self.find(t, name) {
+      h.set_data(Rc::new(asset));
+      return h.cast();
+    }
+    let h = Handle::new(name, t, Some(Rc::new(asset)), LoadState::Loaded);
+    self.handles.entry(t).or_default().push(h.clone());
+    h.cast()
+  }
+
+  // returns a handle immediately, check `state` before using it
+  pub fn load_async<T: Any>(&mut self, path: &str) -> Result<Handle<T>> {
+    let t = TypeIdNamed::of::<T>();
+    let loader = find_loader(t)?;
+    if self.headless || path.contains('#') {
+      return self.load(path);
+    }
+    if let Some(h) = self.find(t, path) {
+      return Ok(h.cast());
+    }
+    trace!("Queued '{}' from '{}'.", t.name, path);
+    match loader.decode {
+      Some(_) => self
+        .workers
+        .get_or_insert_with(Workers::new)
+        .jobs
+        .send((loader, path.to_string()))?,
+      None => self.queued.push((t, path.to_string())),
+    }
+    let h = Handle::new(path, t, None, LoadState::Loading);
+    h.slot.guid.set(self.import(path));
+    self.handles.entry(t).or_default().push(h.clone());
+    Ok(h.cast())
+  }
+
+  pub fn state<T: ?Sized>(&self, handle: &Handle<T>) -> LoadState {
+    handle.state()
+  }
+
+  // None for assets that havent been given a guid
+  pub fn guid(&self, path: &str) -> Option<Guid> {
+    // sub assets share the guid of their file
+    self.db.find(path.split('#').next().unwrap())
+  }
+
+  // gives loose files a guid on first load when the db is writable, so saving handles never writes
+  fn import(&mut self, file: &str) -> Option<Guid> {
+    if self.headless || file.starts_with(BUILTIN) {
+      return self.db.find(file);
+    }
+    match self.db.guid(file) {
+      Ok(guid) => Some(guid),
+      Err(e) => {
+        trace!("No guid for '{}'. {}", file, e);
+        None
       }
-    })
+    }
+  }
+
+  // reloads the asset on the next update, eg. after changing its settings
+  pub fn reload(&mut self, name: &str) {
+    self.reloads.insert(name.to_string());
+  }
+
+  // reloads the asset whenever `file` changes
+  pub fn add_dependency(&mut self, name: &str, file: &str) {
+    if file != name {
+      self
+        .dependents
+        .entry(file.to_string())
+        .or_default()
+        .insert(name.to_string());
+    }
+  }
+
+  // frees the asset even if handles to it remain
+  pub fn unload<T: ?Sized>(&mut self, handle: &Handle<T>) {
+    for v in self.handles.values_mut() {
+      v.retain(|h| !Rc::ptr_eq(&h.slot, &handle.slot));
+    }
+    *handle.slot.data.borrow_mut() = None;
+    handle.slot.state.set(LoadState::Unloaded);
+    trace!("Unloaded '{}'.", handle.name);
+  }
+
+  // frees every asset no handle outside the cache refers to, assets still loading are kept
+  pub fn unload_unused(&mut self) {
+    for v in self.handles.values_mut() {
+      v.retain(|h| {
+        let used = Rc::strong_count(&h.slot) > 1 || h.state() == LoadState::Loading;
+        if !used {
+          trace!("Unloaded '{}'.", h.name);
+        }
+        used
+      });
+    }
+  }
+
+  pub fn memory_report(&self) -> Vec<MemoryReport> {
+    self
+      .handles
+      .iter()
+      .map(|(t, v)| {
+        let mut memory = AssetMemory::default();
+        if let Some(f) = find_loader(*t).ok().and_then(|l| l.memory) {
+          for data in v.iter().filter_map(|h| h.slot.data.borrow().clone()) {
+            let m = f(data.as_ref());
+            memory.cpu += m.cpu;
+            memory.gpu += m.gpu;
+          }
+        }
+        MemoryReport {
+          id: *t,
+          count: v.len(),
+          memory,
+        }
+      })
+      .collect()
+  }
+
+  // number of assets still loading, for loading screens
+  pub fn pending(&self) -> usize {
+    self
+      .handles
+      .values()
+      .flatten()
+      .filter(|h| h.state() == LoadState::Loading)
+      .count()
   }
 
   pub fn get<T: Any>(&self) -> Vec<Handle<T>> {
     match self.handles.get(&TypeIdNamed::of::<T>()) {
-      Some(l) => l.iter().map(|h| h.downcast()).collect(),
+      Some(l) => l.iter().map(|h| h.cast()).collect(),
       None => vec![],
     }
   }
+
+  // assets reloaded since the previous frame
+  pub fn events(&self) -> &[AssetEvent] {
+    &self.events
+  }
+
+  fn find(&self, t: TypeIdNamed, name: &str) -> Option<&Handle<dyn Any>> {
+    self.handles.get(&t)?.iter().find(|h| h.name == name)
+  }
+
+  fn finish(&self, t: TypeIdNamed, name: &str, data: Result<Rc<dyn Any>>) {
+    if let Some(h) = self.find(t, name) {
+      match data {
+        Ok(data) => {
+          trace!("Loaded '{}'.", name);
+          h.set_data(data);
+        }
+        Err(e) => {
+          error!("Couldnt load '{}'. {}", name, e);
+          h.slot.state.set(LoadState::Failed);
+        }
+      }
+    }
+  }
+
+  fn changed(&mut self) -> HashSet<String> {
+    let mut changed = HashSet::new();
+    let mut rescan = false;
+    if let Some(w) = &self.watcher {
+      for event in w.rx.try_iter() {
+        // files may have moved
+        if let Ok(e) = &event {
+          rescan |= matches!(
+            e.kind,
+            EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))
+          );
+        }
+        match event {
+          Ok(e) if matches!(e.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+            changed.extend(e.paths.iter().filter_map(|p| {
+              let p = w.roots.iter().find_map(|r| p.strip_prefix(r).ok())?;
+              let p = p.to_str()?.replace('\\', "/");
+              // changed settings reload the asset, a new guid doesnt change it
+              match p.strip_suffix(".meta") {
+                Some(_) if vfs::read(&p).is_ok_and(|d| asset_db::only_guid(&d)) => None,
+                Some(file) => Some(file.to_string()),
+                None => Some(p),
+              }
+            }));
+          }
+          Ok(_) => {}
+          Err(e) => error!("Asset watcher error. {}", e),
+        }
+      }
+    }
+    if rescan {
+      if let Err(e) = self.db.scan() {
+        error!("Couldnt scan assets. {}", e);
+      }
+    }
+    changed
+  }
+}
+
+// import settings from the asset's .meta file, or the defaults if there isnt one
+pub fn load_settings<S: DeserializeOwned + Default>(path: &str) -> Result<S> {
+  let meta = format!("{}.meta", path);
+  if !vfs::exists(&meta) {
+    return Ok(S::default());
+  }
+  Ok(serde_json::from_slice(&vfs::read(&meta)?)?)
+}
+
+pub fn save_settings<S: Serialize>(path: &str, settings: &S) -> Result {
+  match serde_json::to_value(settings)? {
+    serde_json::Value::Object(mut fields) => {
+      // the cooked version has the old settings
+      fields.insert("cooked".to_string(), serde_json::Value::Null);
+      asset_db::write_meta(path, fields)
+    }
+    _ => Err("Settings must be a struct.".into()),
+  }
+}
+
+// the loader for a file extension
+pub fn loader_for(path: &str) -> Result<&'static AssetLoader> {
+  let ext = Path::new(path)
+    .extension()
+    .and_then(|e| e.to_str())
+    .ok_or_else(|| format!("'{}' has no extension.", path))?
+    .to_lowercase();
+  Ok(
+    ASSET_LOADERS
+      .iter()
+      .find(|l| l.exts.contains(&ext.as_str()))
+      .ok_or_else(|| format!("No loader for '.{}' files.", ext))?,
+  )
+}
+
+pub const COOKED_DIR: &str = ".cooked";
+// bump when a cooked format changes, older cooked files are then ignored
+pub const COOK_VERSION: u32 = 1;
+
+// written to an asset's .meta by phosphor-cook, saving import settings clears it
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct CookStamp {
+  // of the source when it was cooked
+  pub size: u64,
+  pub mtime: u64,
+  // of the source and its import settings, only phosphor-cook hashes
+  pub hash: u64,
+}
+
+impl CookStamp {
+  // archives dont keep mtimes so only the size is checked there
+  pub fn matches(&self, (size, mtime): (u64, Option<u64>)) -> bool {
+    self.size == size && mtime.unwrap_or(self.mtime) == self.mtime
+  }
+}
+
+#[derive(Deserialize)]
+struct CookMeta {
+  guid: Option<Guid>,
+  cooked: Option<CookStamp>,
+}
+
+// where the cooked version of an asset is kept
+pub fn cooked_path(guid: Guid) -> String {
+  format!("{}/v{}-{}", COOKED_DIR, COOK_VERSION, guid)
+}
+
+// the guid and stamp from the asset's .meta
+pub fn cook_stamp(path: &str) -> Option<(Guid, CookStamp)> {
+  let meta: CookMeta = serde_json::from_slice(&vfs::read(&format!("{}.meta", path)).ok()?).ok()?;
+  Some((meta.guid?, meta.cooked?))
+}
+
+pub fn save_cook_stamp(path: &str, stamp: &CookStamp) -> Result {
+  let mut fields = serde_json::Map::new();
+  fields.insert("cooked".to_string(), serde_json::to_value(stamp)?);
+  asset_db::write_meta(path, fields)
+}
+
+// the cooked version of an asset, if it was cooked from the source as it is now
+fn find_cooked(path: &str) -> Option<String> {
+  let (guid, stamp) = cook_stamp(path)?;
+  let cooked = cooked_path(guid);
+  (stamp.matches(vfs::stat(path).ok()?) && vfs::exists(&cooked)).then_some(cooked)
+}
+
+// uses the cooked version if there is one
+fn decode(loader: &AssetLoader, path: &str) -> Result<Decoded> {
+  if let Some(uncook) = loader.uncook {
+    if let Some(cooked) = find_cooked(path) {
+      match uncook(&vfs::read(&cooked)?) {
+        Ok(data) => {
+          trace!("Using cooked '{}'.", path);
+          return Ok(data);
+        }
+        Err(e) => warn!("Cooked '{}' is outdated, recook it. {}", path, e),
+      }
+    }
+  }
+  (loader.decode.ok_or("Asset type has no decode step.")?)(path)
+}
+
+fn load_data(loader: &AssetLoader, world: &mut World, path: &str) -> Result<Rc<dyn Any>> {
+  match loader.upload {
+    Some(upload) if loader.uncook.is_some() => upload(world, decode(loader, path)?),
+    _ => (loader.loader)(world, path),
+  }
+}
+
+fn find_loader(t: TypeIdNamed) -> Result<&'static AssetLoader> {
+  ASSET_LOADERS
+    .iter()
+    .find(|l| l.id == t)
+    .ok_or_else(|| format!("Unknown asset type '{}'.", t.name).into())
+}
+
+impl Workers {
+  fn new() -> Self {
+    let (jobs, rx) = channel::<Job>();
+    let (tx, done) = channel();
+    let rx = Arc::new(Mutex::new(rx));
+    let n = thread::available_parallelism().map_or(2, |n| n.get().min(4));
+    for i in 0..n {
+      let (rx, tx) = (rx.clone(), tx.clone());
+      thread::Builder::new()
+        .name(format!("asset worker {}", i))
+        .spawn(move || loop {
+          // stops when Assets is dropped
+          let job = rx.lock().unwrap().recv();
+          let Ok((loader, name)) = job else { break };
+          let data = decode(loader, &name);
+          if tx.send((loader.id, name, data)).is_err() {
+            break;
+          }
+        })
+        .unwrap();
+    }
+    Self { jobs, done }
+  }
+}
+
+impl AssetWatcher {
+  fn new() -> Result<Self> {
+    let roots = vfs::dirs();
+    let (tx, rx) = channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    for root in roots.iter() {
+      watcher.watch(root, RecursiveMode::Recursive)?;
+    }
+    Ok(Self {
+      roots,
+      rx,
+      _watcher: watcher,
+    })
+  }
+}
+
+pub fn update(world: &mut World) -> Result {
+  let assets = world.get_resource::<Assets>().unwrap();
+  let g = || unsafe { WORLD.get_mut().unwrap() };
+  assets.events.clear();
+  for (t, name) in mem::take(&mut assets.queued) {
+    let data = find_loader(t).and_then(|l| load_data(l, g(), &name));
+    assets.finish(t, &name, data);
+  }
+  if let Some(workers) = &assets.workers {
+    for (t, name, data) in workers.done.try_iter() {
+      let upload = find_loader(t)?.upload.unwrap();
+      assets.finish(t, &name, data.and_then(|d| upload(g(), d)));
+    }
+  }
+  let mut reloads = mem::take(&mut assets.reloads);
+  for file in assets.changed() {
+    if let Some(names) = assets.dependents.get(&file) {
+      reloads.extend(names.iter().cloned());
+    }
+    reloads.insert(file);
+  }
+  for name in reloads {
+    for (t, v) in assets.handles.iter() {
+      if let Some(h) = v.iter().find(|h| h.name == name) {
+        match find_loader(*t).and_then(|l| load_data(l, g(), &name)) {
+          Ok(data) => {
+            h.set_data(data);
+            info!("Reloaded '{}'.", name);
+            assets.events.push(AssetEvent::Modified(*t, name.clone()));
+          }
+          Err(e) => error!("Couldnt reload '{}'. {}", name, e),
+        }
+      }
+    }
+  }
+  Ok(())
+}
+
+// shared between all handles to an asset so reloads are seen everywhere
+// borrows are only held while cloning the data out, so replacing it never frees data in use
+struct Slot {
+  t: TypeIdNamed,
+  data: RefCell<Option<Rc<dyn Any>>>,
+  state: Cell<LoadState>,
+  guid: Cell<Option<Guid>>,
 }
 
-#[derive(Serialize)]
 pub struct Handle<T: ?Sized> {
   pub name: String,
-  #[serde(skip)]
-  data: Rc<T>,
+  slot: Rc<Slot>,
+  _t: PhantomData<Rc<T>>,
+}
+
+thread_local! {
+  static NOT_LOADING: Cell<bool> = const { Cell::new(false) };
+}
+
+// handles deserialized in f arent loaded, they only keep their reference to be serialized again
+// eg. converting a scene to text without its assets
+pub fn without_loading<R>(f: impl FnOnce() -> R) -> R {
+  let prev = NOT_LOADING.with(|n| n.replace(true));
+  let r = f();
+  NOT_LOADING.with(|n| n.set(prev));
+  r
+}
+
+// how handles are saved, "path|guid" or just the path for assets without a guid
+// the path is used when the guid is unknown, scenes from before guids only have paths
+struct AssetRef {
+  guid: Option<Guid>,
+  path: String,
+}
+
+impl AssetRef {
+  fn parse(s: String) -> Self {
+    let guid = s
+      .rsplit_once('|')
+      .and_then(|(path, guid)| Some((path, u128::from_str_radix(guid, 16).ok()?)));
+    match guid {
+      Some((path, guid)) => Self {
+        guid: Some(Guid(guid)),
+        path: path.to_string(),
+      },
+      None => Self {
+        guid: None,
+        path: s,
+      },
+    }
+  }
+}
+
+impl fmt::Display for AssetRef {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.guid {
+      Some(guid) => write!(f, "{}|{}", self.path, guid),
+      None => write!(f, "{}", self.path),
+    }
+  }
+}
+
+impl<T: ?Sized> Serialize for Handle<T> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    let guid = self.slot.guid.get().or_else(|| {
+      unsafe { WORLD.get_mut() }
+        .and_then(|w| w.get_resource::<Assets>())
+        .and_then(|a| a.guid(&self.name))
+    });
+    AssetRef {
+      guid,
+      path: self.name.clone(),
+    }
+    .to_string()
+    .serialize(serializer)
+  }
 }
 
 impl<'de, T: Any> Deserialize<'de> for Handle<T> {
   fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
-    let name: String = Deserialize::deserialize(deserializer)?;
-    Ok(unsafe {
-      WORLD
-        .get_mut()
-        .unwrap()
-        .get_resource::<Assets>()
-        .unwrap()
-        .load(&name)
-        .unwrap()
-    })
+    let r = AssetRef::parse(String::deserialize(deserializer)?);
+    if NOT_LOADING.with(Cell::get) {
+      let h = Handle::new(&r.path, TypeIdNamed::of::<T>(), None, LoadState::Unloaded);
+      h.slot.guid.set(r.guid);
+      return Ok(h.cast());
+    }
+    let assets = unsafe { WORLD.get_mut().unwrap() }
+      .get_resource::<Assets>()
+      .ok_or_else(|| D::Error::custom("No asset manager."))?;
+    let path = match (
+      r.guid.and_then(|g| assets.db.path(g)),
+      r.path.split_once('#'),
+    ) {
+      (Some(file), Some((_, sub))) => format!("{}#{}", file, sub),
+      (Some(file), None) => file.to_string(),
+      (None, _) => r.path,
+    };
+    assets.load(&path).map_err(D::Error::custom)
+  }
+}
+
+impl<T: ?Sized> Handle<T> {
+  pub fn state(&self) -> LoadState {
+    self.slot.state.get()
+  }
+
+  fn set_data(&self, data: Rc<dyn Any>) {
+    *self.slot.data.borrow_mut() = Some(data);
+    self.slot.state.set(LoadState::Loaded);
+  }
+
+  // the type the asset was loaded as
+  pub fn type_id(&self) -> TypeIdNamed {
+    self.slot.t
+  }
+
+  // only for casts that are known to be right
+  fn cast<U: ?Sized>(&self) -> Handle<U> {
+    Handle {
+      name: self.name.clone(),
+      slot: self.slot.clone(),
+      _t: PhantomData,
+    }
+  }
+
+  pub fn downgrade(&self) -> WeakHandle<T> {
+    WeakHandle {
+      name: self.name.clone(),
+      slot: Rc::downgrade(&self.slot),
+      _t: PhantomData,
+    }
   }
 }
 
-impl<T> Deref for Handle<T> {
-  type Target = T;
+impl<T: Any> Handle<T> {
+  // None while loading, if loading failed or when loaded headless
+  // the asset stays alive while the Rc is held, even if it gets reloaded
+  pub fn get(&self) -> Option<Rc<T>> {
+    self.slot.data.borrow().clone()?.downcast().ok()
+  }
+
+  // for assets that have to be there, eg. shaders loaded on startup
+  pub fn loaded(&self) -> Result<Rc<T>> {
+    self
+      .get()
+      .ok_or_else(|| format!("Asset '{}' isnt loaded.", self.name).into())
+  }
 
-  fn deref(&self) -> &T {
-    &self.data
+  // replaces the asset for every handle, eg. after updating a dynamic mesh
+  pub fn set(&self, asset: T) {
+    self.set_data(Rc::new(asset));
   }
 }
 
@@ -97,16 +729,119 @@ impl<T: ?Sized> Clone for Handle<T> {
   fn clone(&self) -> Self {
     Self {
       name: self.name.clone(),
-      data: self.data.clone(),
+      slot: self.slot.clone(),
+      _t: PhantomData,
     }
   }
 }
 
 impl Handle<dyn Any> {
-  pub fn downcast<T: Any>(&self) -> Handle<T> {
-    Handle {
+  fn new(name: &str, t: TypeIdNamed, data: Option<Rc<dyn Any>>, state: LoadState) -> Self {
+    Self {
+      name: name.to_string(),
+      slot: Rc::new(Slot {
+        t,
+        data: RefCell::new(data),
+        state: Cell::new(state),
+        guid: Cell::new(None),
+      }),
+      _t: PhantomData,
+    }
+  }
+
+  // None if the asset isnt a T
+  pub fn downcast<T: Any>(&self) -> Option<Handle<T>> {
+    (self.slot.t.id == TypeId::of::<T>()).then(|| self.cast())
+  }
+}
+
+// doesnt keep the asset loaded, for caches
+pub struct WeakHandle<T: ?Sized> {
+  pub name: String,
+  slot: Weak<Slot>,
+  _t: PhantomData<Rc<T>>,
+}
+
+impl<T: ?Sized> WeakHandle<T> {
+  pub fn upgrade(&self) -> Option<Handle<T>> {
+    Some(Handle {
       name: self.name.clone(),
-      data: self.data.clone().downcast().unwrap(),
+      slot: self.slot.upgrade()?,
+      _t: PhantomData,
+    })
+  }
+}
+
+impl<T: ?Sized> Clone for WeakHandle<T> {
+  fn clone(&self) -> Self {
+    Self {
+      name: self.name.clone(),
+      slot: self.slot.clone(),
+      _t: PhantomData,
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn unused_assets_stay_cached_until_unloaded() {
+    let mut assets = Assets::headless();
+    assets.add("a", 1u32);
+    let b = assets.add("b", 2u32);
+    assets.unload_unused();
+    assert_eq!(assets.get::<u32>().len(), 1);
+    assert_eq!(b.get().as_deref(), Some(&2));
+    assets.unload(&b);
+    assert_eq!(b.state(), LoadState::Unloaded);
+    assert!(b.get().is_none());
+  }
+
+  #[test]
+  fn replaced_asset_stays_alive_while_borrowed() {
+    let mut assets = Assets::headless();
+    let a = assets.add("a", String::from("old"));
+    let old = a.get().unwrap();
+    assets.add("a", String::from("new"));
+    assert_eq!(*old, "old");
+    assert_eq!(*a.get().unwrap(), "new");
+    a.set(String::from("set"));
+    assert_eq!(*assets.get::<String>()[0].get().unwrap(), "set");
+  }
+
+  #[test]
+  fn downcast_checks_type() {
+    let mut assets = Assets::headless();
+    assets.add("a", 1u32);
+    let h = &assets.handles[&TypeIdNamed::of::<u32>()][0];
+    assert!(h.downcast::<String>().is_none());
+    assert_eq!(h.downcast::<u32>().unwrap().get().as_deref(), Some(&1));
+  }
+
+  #[test]
+  fn cooked_path_is_keyed_on_guid() {
+    let guid = Guid(0xabc);
+    assert_eq!(
+      cooked_path(guid),
+      format!("{}/v{}-{:032x}", COOKED_DIR, COOK_VERSION, 0xabc)
+    );
+    assert_ne!(cooked_path(guid), cooked_path(Guid(0xabd)));
+  }
+
+  #[test]
+  fn cook_stamp_matches_source() {
+    let stamp = CookStamp {
+      size: 10,
+      mtime: 20,
+      hash: 30,
+    };
+    assert!(stamp.matches((10, Some(20))));
+    assert!(!stamp.matches((11, Some(20))));
+    assert!(!stamp.matches((10, Some(21))));
+    // packed sources have no mtime
+    assert!(stamp.matches((10, None)));
+    assert!(!stamp.matches((11, None)));
+  }
+}
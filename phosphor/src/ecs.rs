@@ -15,9 +15,12 @@ pub mod stage {
   pub const EVENT: usize = 5;
 }
 
+// (entity, component) pairs of one type
+pub type Components = Vec<(usize, Box<dyn Any>)>;
+
 pub struct World {
-  pub components: HashMap<TypeIdNamed, Vec<(usize, Box<dyn Any>)>>,
-  resources: HashMap<TypeIdNamed, Box<dyn Any>>,
+  pub components: HashMap<TypeIdNamed, Components>,
+  pub(crate) resources: HashMap<TypeIdNamed, Box<dyn Any>>,
   systems: HashMap<usize, Vec<(&'static dyn System, &'static str)>>,
 }
 
@@ -121,16 +124,22 @@ pub struct Entity {
 
 impl Entity {
   pub fn insert<T: Any>(self, component: T) -> Self {
+    self.insert_boxed(TypeIdNamed::of::<T>(), Box::new(component));
+    self
+  }
+
+  pub(crate) fn insert_boxed(self, t: TypeIdNamed, component: Box<dyn Any>) {
     unsafe {
       WORLD
         .get_mut()
         .unwrap()
         .components
-        .push_or_insert(TypeIdNamed::of::<T>(), (self.id, Box::new(component)));
+        .push_or_insert(t, (self.id, component));
     }
-    self
   }
 
+  // entities are ids into the global world, which hands out its components mutably
+  #[allow(clippy::mut_from_ref)]
   pub fn get<T: Any>(&self) -> Vec<&mut T> {
     let t = TypeIdNamed::of::<T>();
     puffin::profile_function!(t.name);
@@ -149,6 +158,7 @@ impl Entity {
     self.get().pop()
   }
 
+  #[allow(clippy::mut_from_ref)]
   pub fn get_all(&self) -> BTreeMap<TypeIdNamed, Vec<&mut Box<dyn Any>>> {
     puffin::profile_function!();
     let mut components = BTreeMap::new();
@@ -1,18 +1,47 @@
-use std::ptr;
-use std::fs::{self, File};
-use std::io::BufReader;
-use std::ffi::{CStr, CString};
+use std::{ptr, mem};
+use std::cell::Cell;
+use std::ffi::CStr;
+use std::path::Path;
 use std::sync::mpsc::Receiver;
+use std::sync::atomic::{AtomicIsize, Ordering};
 use glfw::{Context, WindowHint, WindowEvent, WindowMode};
-use glam::{Mat4, Vec3};
-use image::imageops;
-use obj::{Obj, TexturedVertex};
-use log::{debug, trace, error};
-use shader_prepper::{ResolvedInclude, ResolvedIncludePath};
+use glam::Vec3;
+use image::{ColorType, GenericImageView, ImageBuffer, Pixel};
+use image::imageops::{self, FilterType};
+use log::{debug, warn};
+use serde::{Serialize, Deserialize};
 use crate::ecs::World;
-use crate::{Result, asset};
+use crate::assets::{self, Assets, AssetMemory, Handle};
+use crate::{Result, asset, vfs};
 
 pub use gl;
+pub use mesh_builder::MeshBuilder;
+pub use vertex::{Attribute, VertexLayout, VertexFormat, Vertex};
+pub use cubemap::{Cubemap, Face};
+pub use texture_array::TextureArray;
+pub use shader::{Shader, ShaderError, ShaderMessage};
+pub use uniform_buffer::{UniformBuffer, UniformBlock};
+
+mod mesh_builder;
+mod vertex;
+mod cubemap;
+mod texture_array;
+mod shader;
+mod uniform_buffer;
+
+// gl objects owned by the wrappers below, none of them are Copy or Clone so each object is deleted once when its owner is dropped
+// share one with an Rc (or a Handle for assets) instead
+static LIVE_OBJECTS: AtomicIsize = AtomicIsize::new(0);
+
+// how many gl objects are alive, for leak checks
+pub fn live_objects() -> usize {
+  LIVE_OBJECTS.load(Ordering::Relaxed) as _
+}
+
+fn track(n: isize) {
+  let live = LIVE_OBJECTS.fetch_add(n, Ordering::Relaxed) + n;
+  debug_assert!(live >= 0, "More gl objects deleted than created.");
+}
 
 pub struct Renderer {
   pub glfw: glfw::Glfw,
@@ -67,143 +96,210 @@ impl Renderer {
   }
 }
 
-struct FileIncludeProvider;
-impl shader_prepper::IncludeProvider for FileIncludeProvider {
-  type IncludeContext = ();
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Axis {
+  Y,
+  Z,
+}
 
-  fn resolve_path(
-    &self,
-    path: &str,
-    _: &Self::IncludeContext,
-  ) -> Result<ResolvedInclude<Self::IncludeContext>> {
-    Ok(ResolvedInclude {
-      resolved_path: ResolvedIncludePath(format!("assets/shaders/{}", path)),
-      context: (),
-    })
-  }
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeshSettings {
+  pub scale: f32,
+  pub up: Axis,
+}
 
-  fn get_include(&mut self, resolved: &ResolvedIncludePath) -> Result<String> {
-    Ok(fs::read_to_string(&resolved.0)?)
+impl Default for MeshSettings {
+  fn default() -> Self {
+    Self {
+      scale: 1.0,
+      up: Axis::Y,
+    }
   }
 }
 
-unsafe fn compile_shader(path: &str, ty: u32) -> Result<u32> {
-  trace!("Compiling shader '{}'.", path);
-  let shader = gl::CreateShader(ty);
-  let src = shader_prepper::process_file(path, &mut FileIncludeProvider, ())?
-    .into_iter()
-    .map(|c| c.source)
-    .collect::<Vec<String>>()
-    .join("");
-  gl::ShaderSource(
-    shader,
-    1,
-    &(src.as_bytes().as_ptr().cast()),
-    &(src.len().try_into().unwrap()),
-  );
-  gl::CompileShader(shader);
-  let mut success = 0;
-  gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
-  if success == 0 {
-    let err = CString::from_vec_unchecked(vec![0; 1024]);
-    gl::GetShaderInfoLog(shader, 1024, ptr::null_mut(), err.as_ptr() as _);
-    error!("Failed to compile '{}':\n{}", path, err.to_str()?);
-  }
-  Ok(shader)
+#[asset(
+  decode = decode_mesh,
+  upload = upload_mesh,
+  memory = Mesh::memory,
+  cook = MeshData,
+  ext = ["obj"]
+)]
+pub struct Mesh {
+  pub vert_arr: u32,
+  pub vert_buf: u32,
+  pub idx_buf: u32,
+  pub layout: VertexLayout,
+  // interleaved as described by `layout`
+  pub vertices: Vec<u8>,
+  pub indices: Vec<u32>,
+  pub submeshes: Vec<Submesh>,
+  pub usage: Usage,
 }
 
-#[derive(Copy, Clone)]
-pub struct Shader(pub u32);
+// how often a mesh's buffers are updated
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Usage {
+  Static,
+  // every few frames
+  Dynamic,
+  // every frame
+  Stream,
+}
 
-impl Shader {
-  pub fn new(vert_path: &str, frag_path: &str) -> Result<Self> {
-    unsafe {
-      let vert = compile_shader(vert_path, gl::VERTEX_SHADER)?;
-      let frag = compile_shader(frag_path, gl::FRAGMENT_SHADER)?;
-      let program = gl::CreateProgram();
-      gl::AttachShader(program, vert);
-      gl::AttachShader(program, frag);
-      gl::LinkProgram(program);
-      gl::DeleteShader(vert);
-      gl::DeleteShader(frag);
-      Ok(Self(program))
+impl Usage {
+  fn gl(self) -> u32 {
+    match self {
+      Self::Static => gl::STATIC_DRAW,
+      Self::Dynamic => gl::DYNAMIC_DRAW,
+      Self::Stream => gl::STREAM_DRAW,
     }
   }
+}
 
-  pub fn bind(&self) {
-    unsafe { gl::UseProgram(self.0) }
-  }
+// a range of indices drawn with one material
+pub struct Submesh {
+  pub start: u32,
+  pub count: u32,
+  pub material: Option<MeshMaterial>,
+}
 
-  fn get_loc(&self, name: &str) -> i32 {
-    let c = CString::new(name).unwrap();
-    unsafe { gl::GetUniformLocation(self.0, c.as_ptr() as _) }
-  }
+// from an obj's mtl file
+#[derive(Clone)]
+pub struct MeshMaterial {
+  pub name: String,
+  pub color: Vec3,
+  pub tex: Option<Handle<Texture>>,
+  pub spec: f32,
+}
 
-  pub fn set_mat4(&self, name: &str, val: &Mat4) {
-    unsafe {
-      gl::ProgramUniformMatrix4fv(
-        self.0 as _,
-        self.get_loc(name),
-        1,
-        gl::FALSE,
-        val.to_cols_array().as_ptr(),
-      )
-    }
-  }
+#[derive(Serialize, Deserialize)]
+struct MaterialData {
+  name: String,
+  color: [f32; 3],
+  tex: Option<String>,
+  spec: f32,
+}
 
-  pub fn set_vec3(&self, name: &str, val: &Vec3) {
-    unsafe { gl::ProgramUniform3fv(self.0 as _, self.get_loc(name), 1, val.to_array().as_ptr()) }
-  }
+#[derive(Serialize, Deserialize)]
+struct MeshData {
+  vertices: Vec<Vertex>,
+  indices: Vec<u32>,
+  submeshes: Vec<(u32, u32, Option<MaterialData>)>,
+}
 
-  pub fn set_i32(&self, name: &str, val: &i32) {
-    unsafe {
-      gl::ProgramUniform1i(self.0 as _, self.get_loc(name), *val);
-    }
+fn decode_mesh(path: &str) -> Result<MeshData> {
+  if let Some(name) = path.strip_prefix(assets::BUILTIN) {
+    let m = mesh_builder::builtin(name)?;
+    return Ok(MeshData {
+      vertices: m.vertices(),
+      indices: m.indices.clone(),
+      submeshes: vec![(0, m.indices.len() as _, None)],
+    });
   }
-
-  pub fn set_f32(&self, name: &str, val: &f32) {
-    unsafe {
-      gl::ProgramUniform1f(self.0 as _, self.get_loc(name), *val);
+  let settings: MeshSettings = assets::load_settings(path)?;
+  let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+  let relative = |p: &str| dir.join(p).to_string_lossy().replace('\\', "/");
+  let (models, materials) = tobj::load_obj_buf(
+    &mut vfs::read(path)?.as_slice(),
+    &tobj::LoadOptions {
+      single_index: true,
+      triangulate: true,
+      ..Default::default()
+    },
+    |mtl| match vfs::read(&relative(&mtl.to_string_lossy())) {
+      Ok(data) => tobj::load_mtl_buf(&mut data.as_slice()),
+      Err(_) => Err(tobj::LoadError::OpenFileFailed),
+    },
+  )?;
+  let materials = materials.unwrap_or_else(|e| {
+    warn!("Couldnt load materials for '{}'. {}", path, e);
+    vec![]
+  });
+  // converts to y up
+  let axis = |[x, y, z]: [f32; 3]| match settings.up {
+    Axis::Y => [x, y, z],
+    Axis::Z => [x, z, -y],
+  };
+  let mut data = MeshData {
+    vertices: vec![],
+    indices: vec![],
+    submeshes: vec![],
+  };
+  // tobj splits objects by usemtl
+  for model in models {
+    let mesh = model.mesh;
+    let offset = data.vertices.len() as u32;
+    data.submeshes.push((
+      data.indices.len() as u32,
+      mesh.indices.len() as u32,
+      mesh
+        .material_id
+        .and_then(|i| materials.get(i))
+        .map(|m| MaterialData {
+          name: m.name.clone(),
+          color: m.diffuse.unwrap_or([0.8; 3]),
+          tex: m.diffuse_texture.as_deref().map(relative),
+          spec: m.specular.map_or(0.5, |[r, g, b]| (r + g + b) / 3.0),
+        }),
+    ));
+    data.indices.extend(mesh.indices.iter().map(|i| i + offset));
+    for i in 0..mesh.positions.len() / 3 {
+      let v3 = |v: &[f32]| {
+        v.get(i * 3..i * 3 + 3)
+          .map_or([0.0; 3], |v| [v[0], v[1], v[2]])
+      };
+      data.vertices.push(Vertex {
+        pos: axis(v3(&mesh.positions).map(|p| p * settings.scale)),
+        uv: mesh
+          .texcoords
+          .get(i * 2..i * 2 + 2)
+          .map_or([0.0; 2], |v| [v[0], v[1]]),
+        normal: axis(v3(&mesh.normals)),
+      });
     }
   }
+  Ok(data)
 }
 
-#[repr(C)]
-#[derive(Clone)]
-pub struct Vertex {
-  pub pos: [f32; 3],
-  pub uv: [f32; 2],
-  pub normal: [f32; 3],
+fn upload_mesh(world: &mut World, data: MeshData) -> Result<Mesh> {
+  let assets = world.get_resource::<Assets>().unwrap();
+  let mut submeshes = vec![];
+  for (start, count, material) in data.submeshes {
+    let material = match material {
+      Some(m) => Some(MeshMaterial {
+        name: m.name,
+        color: Vec3::from(m.color),
+        tex: m.tex.map(|t| assets.load(&t)).transpose()?,
+        spec: m.spec,
+      }),
+      None => None,
+    };
+    submeshes.push(Submesh {
+      start,
+      count,
+      material,
+    });
+  }
+  Ok(Mesh::new(&data.vertices, &data.indices).with_submeshes(submeshes))
 }
 
-#[asset(load_mesh)]
-#[derive(Clone)]
-pub struct Mesh {
-  pub vert_arr: u32,
-  pub vert_buf: u32,
-  pub idx_buf: u32,
-  pub vertices: Vec<Vertex>,
-  pub indices: Vec<u32>,
-}
+impl Mesh {
+  pub fn new<V: VertexFormat>(vertices: &[V], indices: &[u32]) -> Self {
+    Self::with_usage(vertices, indices, Usage::Static)
+  }
 
-fn load_mesh(_: &mut World, path: &str) -> Result<Mesh> {
-  let obj: Obj<TexturedVertex, u32> = obj::load_obj(BufReader::new(File::open(path)?))?;
-  Ok(Mesh::new(
-    &obj
-      .vertices
-      .iter()
-      .map(|v| Vertex {
-        pos: v.position,
-        uv: [v.texture[0], v.texture[1]],
-        normal: v.normal,
-      })
-      .collect::<Vec<_>>(),
-    &obj.indices,
-  ))
-}
+  pub fn with_usage<V: VertexFormat>(vertices: &[V], indices: &[u32], usage: Usage) -> Self {
+    Self::from_raw(V::layout(), vertex::as_bytes(vertices), indices, usage)
+  }
 
-impl Mesh {
-  pub fn new(vertices: &[Vertex], indices: &[u32]) -> Self {
+  // `vertices` is interleaved as described by `layout`
+  pub fn from_raw(layout: VertexLayout, vertices: &[u8], indices: &[u32], usage: Usage) -> Self {
+    assert_eq!(
+      vertices.len() % layout.stride(),
+      0,
+      "Vertex data doesnt match the layout."
+    );
     unsafe {
       let mut vert_arr = 0;
       gl::GenVertexArrays(1, &mut vert_arr);
@@ -213,9 +309,9 @@ impl Mesh {
       gl::BindBuffer(gl::ARRAY_BUFFER, vert_buf);
       gl::BufferData(
         gl::ARRAY_BUFFER,
-        (vertices.len() * 32) as _,
+        vertices.len() as _,
         vertices.as_ptr() as _,
-        gl::STATIC_DRAW,
+        usage.gl(),
       );
       let mut idx_buf = 0;
       gl::GenBuffers(1, &mut idx_buf);
@@ -224,24 +320,144 @@ impl Mesh {
         gl::ELEMENT_ARRAY_BUFFER,
         (indices.len() * 4) as _,
         indices.as_ptr() as _,
-        gl::STATIC_DRAW,
+        usage.gl(),
       );
-      gl::EnableVertexAttribArray(0);
-      gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 32, 0 as _);
-      gl::EnableVertexAttribArray(1);
-      gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, 32, 12 as _);
-      gl::EnableVertexAttribArray(2);
-      gl::VertexAttribPointer(2, 3, gl::FLOAT, gl::FALSE, 32, 20 as _);
+      layout.apply();
+      track(3);
       Self {
         vert_arr,
         vert_buf,
         idx_buf,
+        layout,
         vertices: vertices.to_vec(),
         indices: indices.to_vec(),
+        submeshes: vec![Submesh {
+          start: 0,
+          count: indices.len() as _,
+          material: None,
+        }],
+        usage,
       }
     }
   }
 
+  pub fn memory(&self) -> AssetMemory {
+    let bytes = self.vertices.len() + self.indices.len() * 4;
+    AssetMemory {
+      cpu: bytes,
+      gpu: bytes,
+    }
+  }
+
+  pub fn vertex_count(&self) -> usize {
+    self.vertices.len() / self.layout.stride()
+  }
+
+  // reads the position of every vertex, eg for colliders
+  pub fn positions(&self) -> Vec<Vec3> {
+    let Some(offset) = self.layout.offset(Attribute::Position) else {
+      return vec![];
+    };
+    self
+      .vertices
+      .chunks_exact(self.layout.stride())
+      .map(|v| {
+        Vec3::from_array([0, 1, 2].map(|i| {
+          let at = offset + i * 4;
+          f32::from_ne_bytes(v[at..at + 4].try_into().unwrap())
+        }))
+      })
+      .collect()
+  }
+
+  // overwrites vertices from `start`, growing the buffer if they dont fit
+  pub fn update_vertices<V: VertexFormat>(&mut self, start: usize, vertices: &[V]) {
+    self.check_format::<V>();
+    let stride = self.layout.stride();
+    self.update_raw(start * stride, vertex::as_bytes(vertices));
+  }
+
+  // overwrites bytes of the vertex buffer from `start`
+  pub fn update_raw(&mut self, start: usize, data: &[u8]) {
+    let end = start + data.len();
+    if end > self.vertices.len() {
+      self.vertices.resize(end, 0);
+    }
+    self.vertices[start..end].copy_from_slice(data);
+    unsafe {
+      gl::BindBuffer(gl::ARRAY_BUFFER, self.vert_buf);
+    }
+    update_buffer(gl::ARRAY_BUFFER, &self.vertices, start, end, self.usage);
+  }
+
+  pub fn update_indices(&mut self, start: usize, indices: &[u32]) {
+    let end = start + indices.len();
+    if end > self.indices.len() {
+      self.indices.resize(end, 0);
+    }
+    self.indices[start..end].copy_from_slice(indices);
+    unsafe {
+      // the index buffer binding is part of the vao
+      gl::BindVertexArray(self.vert_arr);
+      gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.idx_buf);
+    }
+    update_buffer(
+      gl::ELEMENT_ARRAY_BUFFER,
+      &self.indices,
+      start,
+      end,
+      self.usage,
+    );
+    self.fit_submesh();
+  }
+
+  // replaces all vertices, shrinking the buffer if needed
+  pub fn set_vertices<V: VertexFormat>(&mut self, vertices: &[V]) {
+    self.check_format::<V>();
+    self.vertices = vertex::as_bytes(vertices).to_vec();
+    unsafe {
+      gl::BindBuffer(gl::ARRAY_BUFFER, self.vert_buf);
+    }
+    update_buffer(gl::ARRAY_BUFFER, &self.vertices, 0, usize::MAX, self.usage);
+  }
+
+  pub fn set_indices(&mut self, indices: &[u32]) {
+    self.indices = indices.to_vec();
+    unsafe {
+      gl::BindVertexArray(self.vert_arr);
+      gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.idx_buf);
+    }
+    update_buffer(
+      gl::ELEMENT_ARRAY_BUFFER,
+      &self.indices,
+      0,
+      usize::MAX,
+      self.usage,
+    );
+    self.fit_submesh();
+  }
+
+  fn check_format<V: VertexFormat>(&self) {
+    assert_eq!(
+      V::ATTRIBUTES,
+      self.layout.attributes.as_slice(),
+      "Vertex format doesnt match the layout of the mesh."
+    );
+  }
+
+  // a single submesh keeps covering every index
+  fn fit_submesh(&mut self) {
+    if let [submesh] = self.submeshes.as_mut_slice() {
+      submesh.start = 0;
+      submesh.count = self.indices.len() as _;
+    }
+  }
+
+  pub fn with_submeshes(mut self, submeshes: Vec<Submesh>) -> Self {
+    self.submeshes = submeshes;
+    self
+  }
+
   pub fn draw(&self) {
     unsafe {
       gl::BindVertexArray(self.vert_arr);
@@ -253,10 +469,106 @@ impl Mesh {
       );
     }
   }
+
+  pub fn draw_submesh(&self, i: usize) {
+    let submesh = &self.submeshes[i];
+    unsafe {
+      gl::BindVertexArray(self.vert_arr);
+      gl::DrawElements(
+        gl::TRIANGLES,
+        submesh.count as _,
+        gl::UNSIGNED_INT,
+        (submesh.start as usize * 4) as _,
+      );
+    }
+  }
+}
+
+// uploads data[start..end] to the bound buffer, reallocating it when `end` is past its size
+fn update_buffer<T>(target: u32, data: &[T], start: usize, end: usize, usage: Usage) {
+  let size = mem::size_of::<T>();
+  unsafe {
+    let mut len = 0;
+    gl::GetBufferParameteriv(target, gl::BUFFER_SIZE, &mut len);
+    if end > data.len() || mem::size_of_val(data) as i32 != len {
+      gl::BufferData(
+        target,
+        mem::size_of_val(data) as _,
+        data.as_ptr() as _,
+        usage.gl(),
+      );
+    } else {
+      gl::BufferSubData(
+        target,
+        (start * size) as _,
+        ((end - start) * size) as _,
+        data[start..].as_ptr() as _,
+      );
+    }
+  }
+}
+
+impl Drop for Mesh {
+  fn drop(&mut self) {
+    unsafe {
+      gl::DeleteVertexArrays(1, &self.vert_arr);
+      gl::DeleteBuffers(1, &self.vert_buf);
+      gl::DeleteBuffers(1, &self.idx_buf);
+    }
+    track(-3);
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Filter {
+  Linear,
+  Nearest,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Wrap {
+  Clamp,
+  Repeat,
+  Mirror,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextureSettings {
+  // false for data like normal maps
+  pub srgb: bool,
+  pub filter: Filter,
+  pub wrap: Wrap,
+  pub mipmaps: bool,
+  // 1 disables it, clamped to what the driver supports
+  pub anisotropy: f32,
+  pub flip: bool,
 }
 
-#[derive(Copy, Clone)]
-#[asset(load_tex)]
+impl Default for TextureSettings {
+  fn default() -> Self {
+    Self {
+      srgb: true,
+      filter: Filter::Linear,
+      wrap: Wrap::Clamp,
+      mipmaps: false,
+      anisotropy: 1.0,
+      flip: true,
+    }
+  }
+}
+
+// from EXT_texture_filter_anisotropic, core since 4.6
+const TEXTURE_MAX_ANISOTROPY: u32 = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: u32 = 0x84FF;
+
+#[asset(
+  decode = decode_tex,
+  upload = upload_tex,
+  memory = Texture::memory,
+  cook = TextureData,
+  ext = ["png", "jpg", "jpeg", "bmp", "tga", "hdr", "exr"]
+)]
 pub struct Texture {
   pub id: u32,
   pub width: u32,
@@ -264,19 +576,71 @@ pub struct Texture {
   pub iformat: u32,
   pub format: u32,
   pub typ: u32,
+  // mip levels with storage, for memory
+  levels: Cell<u32>,
+}
+
+// mip levels, largest first, rgba8 or rgba32f for hdr images
+#[derive(Serialize, Deserialize)]
+struct TextureData {
+  width: u32,
+  height: u32,
+  hdr: bool,
+  levels: Vec<Vec<u8>>,
+  settings: TextureSettings,
+}
+
+fn decode_tex(path: &str) -> Result<TextureData> {
+  decode_image(&vfs::read(path)?, assets::load_settings(path)?)
+}
+
+fn decode_image(data: &[u8], settings: TextureSettings) -> Result<TextureData> {
+  let img = image::load_from_memory(data)?;
+  let (width, height) = img.dimensions();
+  let hdr = matches!(img.color(), ColorType::Rgb32F | ColorType::Rgba32F);
+  let levels = if hdr {
+    mip_levels(img.to_rgba32f(), &settings)
+      .into_iter()
+      .map(|l| l.iter().flat_map(|f| f.to_ne_bytes()).collect())
+      .collect()
+  } else {
+    mip_levels(img.to_rgba8(), &settings)
+  };
+  Ok(TextureData {
+    width,
+    height,
+    hdr,
+    levels,
+    settings,
+  })
+}
+
+fn mip_levels<P: Pixel + 'static>(
+  mut img: ImageBuffer<P, Vec<P::Subpixel>>,
+  settings: &TextureSettings,
+) -> Vec<Vec<P::Subpixel>> {
+  if settings.flip {
+    imageops::flip_vertical_in_place(&mut img);
+  }
+  let mut levels = vec![];
+  if settings.mipmaps {
+    let mut level = img.clone();
+    while level.width() > 1 || level.height() > 1 {
+      level = imageops::resize(
+        &level,
+        (level.width() / 2).max(1),
+        (level.height() / 2).max(1),
+        FilterType::Triangle,
+      );
+      levels.push(level.to_vec());
+    }
+  }
+  levels.insert(0, img.into_raw());
+  levels
 }
 
-fn load_tex(_: &mut World, path: &str) -> Result<Texture> {
-  let mut img = image::open(path)?.to_rgba8();
-  imageops::flip_vertical_in_place(&mut img);
-  Ok(Texture::new(
-    img.as_ptr(),
-    img.width(),
-    img.height(),
-    gl::SRGB_ALPHA,
-    gl::RGBA,
-    gl::UNSIGNED_BYTE,
-  ))
+fn upload_tex(_: &mut World, data: TextureData) -> Result<Texture> {
+  Ok(Texture::from_data(data))
 }
 
 impl Texture {
@@ -291,6 +655,7 @@ impl Texture {
     unsafe {
       let mut tex = 0;
       gl::GenTextures(1, &mut tex);
+      track(1);
       gl::BindTexture(gl::TEXTURE_2D, tex);
       gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as _);
       gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as _);
@@ -315,10 +680,51 @@ impl Texture {
         iformat,
         format,
         typ,
+        levels: Cell::new(1),
       }
     }
   }
 
+  // decodes an image file that is already in memory, eg. one embedded in a gltf
+  pub fn from_memory(data: &[u8], settings: TextureSettings) -> Result<Self> {
+    Ok(Self::from_data(decode_image(data, settings)?))
+  }
+
+  fn from_data(data: TextureData) -> Self {
+    let settings = data.settings;
+    let (iformat, typ) = tex_format(data.hdr, settings.srgb);
+    let tex = Texture::new(
+      data.levels[0].as_ptr(),
+      data.width,
+      data.height,
+      iformat,
+      gl::RGBA,
+      typ,
+    );
+    let (mut w, mut h) = (data.width, data.height);
+    for (i, level) in data.levels.iter().enumerate().skip(1) {
+      (w, h) = ((w / 2).max(1), (h / 2).max(1));
+      unsafe {
+        gl::TexImage2D(
+          gl::TEXTURE_2D,
+          i as _,
+          iformat as _,
+          w as _,
+          h as _,
+          0,
+          gl::RGBA,
+          typ,
+          level.as_ptr() as _,
+        );
+      }
+    }
+    tex.levels.set(data.levels.len() as _);
+    tex.set_wrap(settings.wrap);
+    tex.set_filter(settings.filter, settings.mipmaps);
+    tex.set_anisotropy(settings.anisotropy);
+    tex
+  }
+
   pub fn empty() -> Self {
     Self::new(
       ptr::null(),
@@ -337,6 +743,65 @@ impl Texture {
     }
   }
 
+  pub fn set_filter(&self, filter: Filter, mipmaps: bool) {
+    set_filter(gl::TEXTURE_2D, self.id, filter, mipmaps);
+  }
+
+  pub fn set_wrap(&self, wrap: Wrap) {
+    set_wrap(gl::TEXTURE_2D, self.id, wrap);
+  }
+
+  pub fn set_anisotropy(&self, anisotropy: f32) {
+    set_anisotropy(gl::TEXTURE_2D, self.id, anisotropy);
+  }
+
+  pub fn generate_mipmaps(&self) {
+    unsafe {
+      gl::BindTexture(gl::TEXTURE_2D, self.id);
+      gl::GenerateMipmap(gl::TEXTURE_2D);
+    }
+    self.levels.set(mip_count(self.width, self.height));
+  }
+
+  pub fn memory(&self) -> AssetMemory {
+    AssetMemory {
+      cpu: 0,
+      gpu: mip_memory(self.width, self.height, self.levels.get(), self.iformat),
+    }
+  }
+
+  // copies a mip level back to the cpu, in the format and type the texture was created with
+  pub fn read_pixels(&self, level: u32) -> Vec<u8> {
+    let components = match self.format {
+      gl::RED | gl::DEPTH_COMPONENT => 1,
+      gl::RG => 2,
+      gl::RGB => 3,
+      _ => 4,
+    };
+    let size = match self.typ {
+      gl::UNSIGNED_BYTE | gl::BYTE => 1,
+      gl::HALF_FLOAT | gl::UNSIGNED_SHORT | gl::SHORT => 2,
+      _ => 4,
+    };
+    let (w, h) = (
+      (self.width >> level).max(1) as usize,
+      (self.height >> level).max(1) as usize,
+    );
+    let mut data = vec![0u8; w * h * components * size];
+    unsafe {
+      gl::BindTexture(gl::TEXTURE_2D, self.id);
+      gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+      gl::GetTexImage(
+        gl::TEXTURE_2D,
+        level as _,
+        self.format,
+        self.typ,
+        data.as_mut_ptr() as _,
+      );
+    }
+    data
+  }
+
   pub fn resize(&mut self, width: u32, height: u32) {
     unsafe {
       self.bind(0);
@@ -354,10 +819,92 @@ impl Texture {
       self.width = width;
       self.height = height;
     }
+    // only the first level is reallocated
+    self.levels.set(1);
+  }
+}
+
+// internal format and type for decoded images
+fn tex_format(hdr: bool, srgb: bool) -> (u32, u32) {
+  match (hdr, srgb) {
+    (true, _) => (gl::RGBA16F, gl::FLOAT),
+    (false, true) => (gl::SRGB_ALPHA, gl::UNSIGNED_BYTE),
+    (false, false) => (gl::RGBA8, gl::UNSIGNED_BYTE),
+  }
+}
+
+// levels in a full mip chain, down to 1x1
+fn mip_count(width: u32, height: u32) -> u32 {
+  32 - width.max(height).max(1).leading_zeros()
+}
+
+// bytes of every level, each half the size of the last
+fn mip_memory(width: u32, height: u32, levels: u32, iformat: u32) -> usize {
+  let texels: usize = (0..levels)
+    .map(|i| ((width >> i).max(1) * (height >> i).max(1)) as usize)
+    .sum();
+  texels * texel_size(iformat)
+}
+
+fn texel_size(iformat: u32) -> usize {
+  match iformat {
+    gl::RED | gl::R8 => 1,
+    gl::R16F | gl::RG8 => 2,
+    gl::RGB | gl::RGB8 | gl::SRGB => 3,
+    gl::RGBA16F => 8,
+    gl::RGBA32F => 16,
+    _ => 4,
+  }
+}
+
+fn set_filter(target: u32, id: u32, filter: Filter, mipmaps: bool) {
+  let (min, mag) = match (filter, mipmaps) {
+    (Filter::Linear, false) => (gl::LINEAR, gl::LINEAR),
+    (Filter::Linear, true) => (gl::LINEAR_MIPMAP_LINEAR, gl::LINEAR),
+    (Filter::Nearest, false) => (gl::NEAREST, gl::NEAREST),
+    (Filter::Nearest, true) => (gl::NEAREST_MIPMAP_NEAREST, gl::NEAREST),
+  };
+  unsafe {
+    gl::BindTexture(target, id);
+    gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, min as _);
+    gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, mag as _);
+  }
+}
+
+fn set_wrap(target: u32, id: u32, wrap: Wrap) {
+  let wrap = match wrap {
+    Wrap::Clamp => gl::CLAMP_TO_EDGE,
+    Wrap::Repeat => gl::REPEAT,
+    Wrap::Mirror => gl::MIRRORED_REPEAT,
+  };
+  unsafe {
+    gl::BindTexture(target, id);
+    gl::TexParameteri(target, gl::TEXTURE_WRAP_S, wrap as _);
+    gl::TexParameteri(target, gl::TEXTURE_WRAP_T, wrap as _);
+    gl::TexParameteri(target, gl::TEXTURE_WRAP_R, wrap as _);
+  }
+}
+
+fn set_anisotropy(target: u32, id: u32, anisotropy: f32) {
+  unsafe {
+    let mut max = 1.0;
+    gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max);
+    gl::BindTexture(target, id);
+    gl::TexParameterf(
+      target,
+      TEXTURE_MAX_ANISOTROPY,
+      anisotropy.clamp(1.0, max.max(1.0)),
+    );
+  }
+}
+
+impl Drop for Texture {
+  fn drop(&mut self) {
+    unsafe { gl::DeleteTextures(1, &self.id) };
+    track(-1);
   }
 }
 
-#[derive(Copy, Clone)]
 pub struct Framebuffer {
   pub fb: u32,
   pub rb: u32,
@@ -370,6 +917,7 @@ impl Framebuffer {
     unsafe {
       let mut s = Self::new_no_depth();
       gl::GenRenderbuffers(1, &mut s.rb);
+      track(1);
       gl::BindRenderbuffer(gl::RENDERBUFFER, s.rb);
       gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH24_STENCIL8, 0, 0);
       gl::FramebufferRenderbuffer(
@@ -386,6 +934,7 @@ impl Framebuffer {
     unsafe {
       let mut fb = 0;
       gl::GenFramebuffers(1, &mut fb);
+      track(1);
       gl::BindFramebuffer(gl::FRAMEBUFFER, fb);
       Self { fb, rb: 0 }
     }
@@ -423,6 +972,53 @@ impl Framebuffer {
     }
   }
 
+  // eg rendering a point light shadow or reflection probe one face at a time
+  pub fn bind_cube_face(&self, cube: &Cubemap, face: Face, unit: u32) {
+    unsafe {
+      self.bind();
+      gl::FramebufferTexture2D(
+        gl::FRAMEBUFFER,
+        gl::COLOR_ATTACHMENT0 + unit,
+        face.target(),
+        cube.id,
+        0,
+      );
+    }
+  }
+
+  pub fn bind_cube_depth(&self, cube: &Cubemap, face: Face) {
+    unsafe {
+      self.bind();
+      gl::FramebufferTexture2D(
+        gl::FRAMEBUFFER,
+        gl::DEPTH_ATTACHMENT,
+        face.target(),
+        cube.id,
+        0,
+      );
+    }
+  }
+
+  pub fn bind_layer(&self, arr: &TextureArray, layer: u32, unit: u32) {
+    unsafe {
+      self.bind();
+      gl::FramebufferTextureLayer(
+        gl::FRAMEBUFFER,
+        gl::COLOR_ATTACHMENT0 + unit,
+        arr.id,
+        0,
+        layer as _,
+      );
+    }
+  }
+
+  pub fn bind_layer_depth(&self, arr: &TextureArray, layer: u32) {
+    unsafe {
+      self.bind();
+      gl::FramebufferTextureLayer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, arr.id, 0, layer as _);
+    }
+  }
+
   pub fn resize(&self, width: u32, height: u32) {
     unsafe {
       gl::BindRenderbuffer(gl::RENDERBUFFER, self.rb);
@@ -436,6 +1032,22 @@ impl Framebuffer {
   }
 }
 
+// DEFAULT owns nothing
+impl Drop for Framebuffer {
+  fn drop(&mut self) {
+    unsafe {
+      if self.rb != 0 {
+        gl::DeleteRenderbuffers(1, &self.rb);
+        track(-1);
+      }
+      if self.fb != 0 {
+        gl::DeleteFramebuffers(1, &self.fb);
+        track(-1);
+      }
+    }
+  }
+}
+
 pub struct Query(u32);
 
 impl Query {
@@ -443,6 +1055,7 @@ impl Query {
     unsafe {
       let mut id = 0;
       gl::GenQueries(1, &mut id);
+      track(1);
       Self(id)
     }
   }
@@ -475,3 +1088,24 @@ impl Query {
     }
   }
 }
+
+impl Drop for Query {
+  fn drop(&mut self) {
+    unsafe { gl::DeleteQueries(1, &self.0) };
+    track(-1);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn live_objects_counts_created_and_deleted() {
+    let live = live_objects();
+    track(3);
+    assert_eq!(live_objects(), live + 3);
+    track(-3);
+    assert_eq!(live_objects(), live);
+  }
+}
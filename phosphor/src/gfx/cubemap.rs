@@ -0,0 +1,270 @@
+use std::cell::Cell;
+use std::f32::consts::PI;
+use std::path::Path;
+use glam::{Vec3, Vec4};
+use image::Rgba32FImage;
+use serde::{Serialize, Deserialize};
+use crate::ecs::World;
+use crate::assets::{self, AssetMemory};
+use crate::{Result, asset, vfs};
+use super::{TextureSettings, Filter, Wrap};
+
+// in the order gl expects them
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Face {
+  PosX,
+  NegX,
+  PosY,
+  NegY,
+  PosZ,
+  NegZ,
+}
+
+impl Face {
+  pub const ALL: [Self; 6] = [
+    Self::PosX,
+    Self::NegX,
+    Self::PosY,
+    Self::NegY,
+    Self::PosZ,
+    Self::NegZ,
+  ];
+
+  pub fn target(self) -> u32 {
+    gl::TEXTURE_CUBE_MAP_POSITIVE_X + self as u32
+  }
+
+  // direction through a point on the face, u and v in -1..1 from the top left
+  fn dir(self, u: f32, v: f32) -> Vec3 {
+    match self {
+      Self::PosX => Vec3::new(1.0, -v, -u),
+      Self::NegX => Vec3::new(-1.0, -v, u),
+      Self::PosY => Vec3::new(u, 1.0, v),
+      Self::NegY => Vec3::new(u, -1.0, -v),
+      Self::PosZ => Vec3::new(u, -v, 1.0),
+      Self::NegZ => Vec3::new(-u, -v, -1.0),
+    }
+  }
+}
+
+// a .cube file is json, either six face images or an equirectangular image to project, paths are relative to it
+// {"faces": ["px.png", "nx.png", "py.png", "ny.png", "pz.png", "nz.png"]}
+// {"equirect": {"path": "sky.hdr", "size": 512}}
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CubeSource {
+  Faces([String; 6]),
+  Equirect { path: String, size: u32 },
+}
+
+#[asset(
+  decode = decode_cubemap,
+  upload = upload_cubemap,
+  memory = Cubemap::memory,
+  cook = CubemapData,
+  ext = ["cube"]
+)]
+pub struct Cubemap {
+  pub id: u32,
+  pub size: u32,
+  pub iformat: u32,
+  pub format: u32,
+  pub typ: u32,
+  // mip levels with storage, for memory
+  levels: Cell<u32>,
+}
+
+// rgba8 or rgba32f faces
+#[derive(Serialize, Deserialize)]
+struct CubemapData {
+  size: u32,
+  hdr: bool,
+  faces: Vec<Vec<u8>>,
+  settings: TextureSettings,
+}
+
+fn decode_cubemap(path: &str) -> Result<CubemapData> {
+  let settings: TextureSettings = assets::load_settings(path)?;
+  let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+  let relative = |p: &str| dir.join(p).to_string_lossy().replace('\\', "/");
+  match serde_json::from_slice(&vfs::read(path)?)? {
+    CubeSource::Faces(faces) => {
+      let face_settings = TextureSettings {
+        mipmaps: false,
+        flip: false,
+        ..settings.clone()
+      };
+      let faces = faces
+        .iter()
+        .map(|f| super::decode_image(&vfs::read(&relative(f))?, face_settings.clone()))
+        .collect::<Result<Vec<_>>>()?;
+      let (size, hdr) = (faces[0].width, faces[0].hdr);
+      if faces
+        .iter()
+        .any(|f| f.width != size || f.height != size || f.hdr != hdr)
+      {
+        return Err("Cubemap faces must be square and the same size and format.".into());
+      }
+      Ok(CubemapData {
+        size,
+        hdr,
+        faces: faces
+          .into_iter()
+          .map(|mut f| f.levels.swap_remove(0))
+          .collect(),
+        settings,
+      })
+    }
+    CubeSource::Equirect { path, size } => {
+      let img = image::load_from_memory(&vfs::read(&relative(&path))?)?.to_rgba32f();
+      Ok(CubemapData {
+        size,
+        hdr: true,
+        faces: Face::ALL
+          .iter()
+          .map(|f| project_face(&img, *f, size))
+          .collect(),
+        settings,
+      })
+    }
+  }
+}
+
+fn project_face(img: &Rgba32FImage, face: Face, size: u32) -> Vec<u8> {
+  let mut data = Vec::with_capacity((size * size * 16) as _);
+  for y in 0..size {
+    for x in 0..size {
+      let u = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+      let v = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+      let d = face.dir(u, v).normalize();
+      let eu = 0.5 + d.z.atan2(d.x) / (2.0 * PI);
+      let ev = 0.5 - d.y.asin() / PI;
+      data.extend(
+        sample(img, eu, ev)
+          .to_array()
+          .iter()
+          .flat_map(|f| f.to_ne_bytes()),
+      );
+    }
+  }
+  data
+}
+
+// bilinear, wrapping horizontally
+fn sample(img: &Rgba32FImage, u: f32, v: f32) -> Vec4 {
+  let (w, h) = img.dimensions();
+  let x = u * w as f32 - 0.5;
+  let y = (v * h as f32 - 0.5).clamp(0.0, (h - 1) as f32);
+  let (x0, y0) = (x.floor(), y.floor());
+  let (fx, fy) = (x - x0, y - y0);
+  let px = |x: f32, y: f32| {
+    let x = (x as i64).rem_euclid(w as i64) as u32;
+    let y = (y as u32).min(h - 1);
+    Vec4::from(img.get_pixel(x, y).0)
+  };
+  let top = px(x0, y0).lerp(px(x0 + 1.0, y0), fx);
+  let bottom = px(x0, y0 + 1.0).lerp(px(x0 + 1.0, y0 + 1.0), fx);
+  top.lerp(bottom, fy)
+}
+
+fn upload_cubemap(_: &mut World, data: CubemapData) -> Result<Cubemap> {
+  let settings = data.settings;
+  let (iformat, typ) = super::tex_format(data.hdr, settings.srgb);
+  let cube = Cubemap::new(data.size, iformat, gl::RGBA, typ);
+  for (face, pixels) in Face::ALL.iter().zip(data.faces.iter()) {
+    cube.set_face(*face, pixels.as_ptr());
+  }
+  if settings.mipmaps {
+    cube.generate_mipmaps();
+  }
+  cube.set_filter(settings.filter, settings.mipmaps);
+  cube.set_anisotropy(settings.anisotropy);
+  Ok(cube)
+}
+
+impl Cubemap {
+  pub fn new(size: u32, iformat: u32, format: u32, typ: u32) -> Self {
+    let mut id = 0;
+    unsafe {
+      gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+      gl::GenTextures(1, &mut id);
+    }
+    super::track(1);
+    let mut cube = Self {
+      id,
+      size: 0,
+      iformat,
+      format,
+      typ,
+      levels: Cell::new(1),
+    };
+    cube.resize(size);
+    cube.set_filter(Filter::Linear, false);
+    super::set_wrap(gl::TEXTURE_CUBE_MAP, id, Wrap::Clamp);
+    cube
+  }
+
+  // `data` is one face in the cubemaps format and type
+  pub fn set_face(&self, face: Face, data: *const u8) {
+    unsafe {
+      gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.id);
+      gl::TexImage2D(
+        face.target(),
+        0,
+        self.iformat as _,
+        self.size as _,
+        self.size as _,
+        0,
+        self.format,
+        self.typ,
+        data as _,
+      );
+    }
+  }
+
+  pub fn bind(&self, unit: u32) {
+    unsafe {
+      gl::ActiveTexture(gl::TEXTURE0 + unit);
+      gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.id);
+    }
+  }
+
+  pub fn set_filter(&self, filter: Filter, mipmaps: bool) {
+    super::set_filter(gl::TEXTURE_CUBE_MAP, self.id, filter, mipmaps);
+  }
+
+  pub fn set_anisotropy(&self, anisotropy: f32) {
+    super::set_anisotropy(gl::TEXTURE_CUBE_MAP, self.id, anisotropy);
+  }
+
+  pub fn generate_mipmaps(&self) {
+    unsafe {
+      gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.id);
+      gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+    }
+    self.levels.set(super::mip_count(self.size, self.size));
+  }
+
+  pub fn memory(&self) -> AssetMemory {
+    AssetMemory {
+      cpu: 0,
+      gpu: super::mip_memory(self.size, self.size, self.levels.get(), self.iformat) * 6,
+    }
+  }
+
+  // clears every face
+  pub fn resize(&mut self, size: u32) {
+    self.size = size;
+    self.levels.set(1);
+    for face in Face::ALL {
+      self.set_face(face, std::ptr::null());
+    }
+  }
+}
+
+impl Drop for Cubemap {
+  fn drop(&mut self) {
+    unsafe { gl::DeleteTextures(1, &self.id) };
+    super::track(-1);
+  }
+}
@@ -0,0 +1,514 @@
+use std::f32::consts::{PI, TAU};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use crate::Result;
+use super::{Mesh, Vertex, Attribute, VertexLayout, Usage};
+
+// cpu side geometry, for procedural meshes
+#[derive(Clone, Default)]
+pub struct MeshBuilder {
+  pub positions: Vec<Vec3>,
+  pub uvs: Vec<Vec2>,
+  pub normals: Vec<Vec3>,
+  // xyz is the tangent, w the sign of the bitangent
+  pub tangents: Vec<Vec4>,
+  pub colors: Vec<Vec4>,
+  pub uvs2: Vec<Vec2>,
+  pub joints: Vec<[u16; 4]>,
+  pub weights: Vec<Vec4>,
+  pub indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // returns the index of the vertex
+  pub fn vertex(&mut self, pos: Vec3, uv: Vec2, normal: Vec3) -> u32 {
+    self.positions.push(pos);
+    self.uvs.push(uv);
+    self.normals.push(normal);
+    self.positions.len() as u32 - 1
+  }
+
+  // counter clockwise
+  pub fn triangle(&mut self, a: u32, b: u32, c: u32) {
+    self.indices.extend([a, b, c]);
+  }
+
+  pub fn quad(&mut self, a: u32, b: u32, c: u32, d: u32) {
+    self.indices.extend([a, b, c, a, c, d]);
+  }
+
+  pub fn append(&mut self, other: &Self) {
+    let offset = self.positions.len() as u32;
+    self.positions.extend(&other.positions);
+    self.uvs.extend(&other.uvs);
+    self.normals.extend(&other.normals);
+    self.tangents.extend(&other.tangents);
+    self.colors.extend(&other.colors);
+    self.uvs2.extend(&other.uvs2);
+    self.joints.extend(&other.joints);
+    self.weights.extend(&other.weights);
+    self
+      .indices
+      .extend(other.indices.iter().map(|i| i + offset));
+  }
+
+  pub fn transform(&mut self, m: Mat4) {
+    let n = m.inverse().transpose();
+    for p in self.positions.iter_mut() {
+      *p = m.transform_point3(*p);
+    }
+    for v in self.normals.iter_mut() {
+      *v = n.transform_vector3(*v).normalize_or_zero();
+    }
+    for t in self.tangents.iter_mut() {
+      *t = m
+        .transform_vector3(t.truncate())
+        .normalize_or_zero()
+        .extend(t.w);
+    }
+  }
+
+  // smooth normals weighted by triangle area
+  pub fn compute_normals(&mut self) {
+    self.normals = vec![Vec3::ZERO; self.positions.len()];
+    for tri in self.indices.chunks_exact(3) {
+      let [a, b, c] = [0, 1, 2].map(|i| self.positions[tri[i] as usize]);
+      let n = (b - a).cross(c - a);
+      for i in tri {
+        self.normals[*i as usize] += n;
+      }
+    }
+    for n in self.normals.iter_mut() {
+      *n = n.normalize_or_zero();
+    }
+  }
+
+  // needs normals and uvs
+  pub fn compute_tangents(&mut self) {
+    let mut tangents = vec![Vec3::ZERO; self.positions.len()];
+    let mut bitangents = vec![Vec3::ZERO; self.positions.len()];
+    for tri in self.indices.chunks_exact(3) {
+      let [a, b, c] = [0, 1, 2].map(|i| tri[i] as usize);
+      let (e1, e2) = (
+        self.positions[b] - self.positions[a],
+        self.positions[c] - self.positions[a],
+      );
+      let (d1, d2) = (self.uvs[b] - self.uvs[a], self.uvs[c] - self.uvs[a]);
+      let det = d1.x * d2.y - d2.x * d1.y;
+      if det.abs() < f32::EPSILON {
+        continue;
+      }
+      let t = (e1 * d2.y - e2 * d1.y) / det;
+      let bt = (e2 * d1.x - e1 * d2.x) / det;
+      for i in [a, b, c] {
+        tangents[i] += t;
+        bitangents[i] += bt;
+      }
+    }
+    self.tangents = tangents
+      .iter()
+      .zip(bitangents.iter())
+      .zip(self.normals.iter())
+      .map(|((t, bt), n)| {
+        // gram-schmidt
+        let t = (*t - *n * n.dot(*t)).normalize_or_zero();
+        let w = if n.cross(t).dot(*bt) < 0.0 { -1.0 } else { 1.0 };
+        t.extend(w)
+      })
+      .collect();
+  }
+
+  pub fn vertices(&self) -> Vec<Vertex> {
+    (0..self.positions.len())
+      .map(|i| Vertex {
+        pos: self.positions[i].to_array(),
+        uv: self.uvs[i].to_array(),
+        normal: self.normals[i].to_array(),
+      })
+      .collect()
+  }
+
+  // position, uv and normal, plus every other attribute that has a value for each vertex
+  pub fn layout(&self) -> VertexLayout {
+    let n = self.positions.len();
+    let mut attributes = vec![Attribute::Position, Attribute::Uv, Attribute::Normal];
+    for (a, len) in [
+      (Attribute::Tangent, self.tangents.len()),
+      (Attribute::Color, self.colors.len()),
+      (Attribute::Uv2, self.uvs2.len()),
+      (Attribute::Joints, self.joints.len()),
+      (Attribute::Weights, self.weights.len()),
+    ] {
+      if len == n && n > 0 {
+        attributes.push(a);
+      }
+    }
+    VertexLayout::new(&attributes)
+  }
+
+  // interleaves the vertices, missing values are zeroed
+  pub fn vertex_data(&self, layout: &VertexLayout) -> Vec<u8> {
+    let mut data = Vec::with_capacity(self.positions.len() * layout.stride());
+    for i in 0..self.positions.len() {
+      for a in layout.attributes.iter() {
+        let v = match a {
+          Attribute::Position => self.positions.get(i).map(|v| v.extend(0.0)),
+          Attribute::Uv => self.uvs.get(i).map(|v| v.extend(0.0).extend(0.0)),
+          Attribute::Normal => self.normals.get(i).map(|v| v.extend(0.0)),
+          Attribute::Tangent => self.tangents.get(i).copied(),
+          Attribute::Color => self.colors.get(i).copied(),
+          Attribute::Uv2 => self.uvs2.get(i).map(|v| v.extend(0.0).extend(0.0)),
+          Attribute::Weights => self.weights.get(i).copied(),
+          Attribute::Joints => {
+            for j in self.joints.get(i).copied().unwrap_or_default() {
+              data.extend(j.to_ne_bytes());
+            }
+            continue;
+          }
+        };
+        for x in &v.unwrap_or_default().to_array()[..a.components()] {
+          data.extend(x.to_ne_bytes());
+        }
+      }
+    }
+    data
+  }
+
+  pub fn build(&self) -> Mesh {
+    self.build_with(self.layout(), Usage::Static)
+  }
+
+  pub fn build_with(&self, layout: VertexLayout, usage: Usage) -> Mesh {
+    Mesh::from_raw(
+      layout.clone(),
+      &self.vertex_data(&layout),
+      &self.indices,
+      usage,
+    )
+  }
+
+  // flat on xz, facing up
+  pub fn plane(size: f32, subdivisions: u32) -> Self {
+    let mut m = Self::new();
+    let n = subdivisions + 1;
+    for z in 0..=n {
+      for x in 0..=n {
+        let uv = Vec2::new(x as f32, z as f32) / n as f32;
+        m.vertex(Vec3::new(uv.x - 0.5, 0.0, 0.5 - uv.y) * size, uv, Vec3::Y);
+      }
+    }
+    for z in 0..n {
+      for x in 0..n {
+        let i = z * (n + 1) + x;
+        m.quad(i, i + 1, i + n + 2, i + n + 1);
+      }
+    }
+    m
+  }
+
+  pub fn cube(size: f32) -> Self {
+    let mut m = Self::new();
+    for (normal, up) in [
+      (Vec3::X, Vec3::Y),
+      (Vec3::NEG_X, Vec3::Y),
+      (Vec3::Y, Vec3::NEG_Z),
+      (Vec3::NEG_Y, Vec3::Z),
+      (Vec3::Z, Vec3::Y),
+      (Vec3::NEG_Z, Vec3::Y),
+    ] {
+      let right = up.cross(normal);
+      let corner =
+        |u: f32, v: f32| (normal + right * (u * 2.0 - 1.0) + up * (v * 2.0 - 1.0)) * size / 2.0;
+      let i = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]
+        .map(|(u, v)| m.vertex(corner(u, v), Vec2::new(u, v), normal));
+      m.quad(i[0], i[1], i[2], i[3]);
+    }
+    m
+  }
+
+  // at least 3 sectors and 2 stacks, fewer dont enclose anything
+  pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> Self {
+    let (sectors, stacks) = (sectors.max(3), stacks.max(2));
+    let mut m = Self::new();
+    for y in 0..=stacks {
+      let v = y as f32 / stacks as f32;
+      let phi = v * PI - PI / 2.0;
+      for x in 0..=sectors {
+        let u = x as f32 / sectors as f32;
+        let n = Vec3::new(
+          phi.cos() * (u * TAU).cos(),
+          phi.sin(),
+          -phi.cos() * (u * TAU).sin(),
+        );
+        m.vertex(n * radius, Vec2::new(u, v), n);
+      }
+    }
+    m.grid(sectors, stacks);
+    m
+  }
+
+  pub fn ico_sphere(radius: f32, subdivisions: u32) -> Self {
+    let t = (1.0 + 5f32.sqrt()) / 2.0;
+    let mut positions: Vec<Vec3> = [
+      [-1.0, t, 0.0],
+      [1.0, t, 0.0],
+      [-1.0, -t, 0.0],
+      [1.0, -t, 0.0],
+      [0.0, -1.0, t],
+      [0.0, 1.0, t],
+      [0.0, -1.0, -t],
+      [0.0, 1.0, -t],
+      [t, 0.0, -1.0],
+      [t, 0.0, 1.0],
+      [-t, 0.0, -1.0],
+      [-t, 0.0, 1.0],
+    ]
+    .iter()
+    .map(|p| Vec3::from(*p).normalize())
+    .collect();
+    let mut tris = vec![
+      [0, 11, 5],
+      [0, 5, 1],
+      [0, 1, 7],
+      [0, 7, 10],
+      [0, 10, 11],
+      [1, 5, 9],
+      [5, 11, 4],
+      [11, 10, 2],
+      [10, 7, 6],
+      [7, 1, 8],
+      [3, 9, 4],
+      [3, 4, 2],
+      [3, 2, 6],
+      [3, 6, 8],
+      [3, 8, 9],
+      [4, 9, 5],
+      [2, 4, 11],
+      [6, 2, 10],
+      [8, 6, 7],
+      [9, 8, 1],
+    ];
+    for _ in 0..subdivisions {
+      let mut mid = |a: u32, b: u32| {
+        positions.push((positions[a as usize] + positions[b as usize]).normalize());
+        positions.len() as u32 - 1
+      };
+      tris = tris
+        .iter()
+        .flat_map(|&[a, b, c]| {
+          let (ab, bc, ca) = (mid(a, b), mid(b, c), mid(c, a));
+          [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+        })
+        .collect();
+    }
+    // unwelded so each triangle can fix its uvs across the seam
+    let mut m = Self::new();
+    for tri in tris {
+      let mut uvs = tri.map(|i| {
+        let n = positions[i as usize];
+        Vec2::new(0.5 + (-n.z).atan2(n.x) / TAU, 0.5 + n.y.asin() / PI)
+      });
+      let max = uvs.iter().map(|uv| uv.x).fold(0.0, f32::max);
+      for uv in uvs.iter_mut() {
+        if max - uv.x > 0.5 {
+          uv.x += 1.0;
+        }
+      }
+      let i = [0, 1, 2].map(|j| {
+        let n = positions[tri[j] as usize];
+        m.vertex(n * radius, uvs[j], n)
+      });
+      m.triangle(i[0], i[1], i[2]);
+    }
+    m
+  }
+
+  pub fn cylinder(radius: f32, height: f32, sectors: u32) -> Self {
+    let sectors = sectors.max(3);
+    let mut m = Self::new();
+    for y in 0..=1 {
+      let v = y as f32;
+      for x in 0..=sectors {
+        let u = x as f32 / sectors as f32;
+        let n = Vec3::new((u * TAU).cos(), 0.0, -(u * TAU).sin());
+        m.vertex(
+          n * radius + Vec3::Y * (v - 0.5) * height,
+          Vec2::new(u, v),
+          n,
+        );
+      }
+    }
+    m.grid(sectors, 1);
+    m.cap(radius, height / 2.0, sectors, true);
+    m.cap(radius, -height / 2.0, sectors, false);
+    m
+  }
+
+  // base on y = -height / 2
+  pub fn cone(radius: f32, height: f32, sectors: u32) -> Self {
+    let sectors = sectors.max(3);
+    let mut m = Self::new();
+    let slope = radius / height;
+    for x in 0..sectors {
+      // a tip per sector so each gets its own normal
+      let (u0, u1) = (x as f32 / sectors as f32, (x + 1) as f32 / sectors as f32);
+      let dir = |u: f32| Vec3::new((u * TAU).cos(), 0.0, -(u * TAU).sin());
+      let normal = |u: f32| (dir(u) + Vec3::Y * slope).normalize();
+      let a = m.vertex(
+        dir(u0) * radius - Vec3::Y * height / 2.0,
+        Vec2::new(u0, 0.0),
+        normal(u0),
+      );
+      let b = m.vertex(
+        dir(u1) * radius - Vec3::Y * height / 2.0,
+        Vec2::new(u1, 0.0),
+        normal(u1),
+      );
+      let tip = m.vertex(
+        Vec3::Y * height / 2.0,
+        Vec2::new((u0 + u1) / 2.0, 1.0),
+        normal((u0 + u1) / 2.0),
+      );
+      m.triangle(a, b, tip);
+    }
+    m.cap(radius, -height / 2.0, sectors, false);
+    m
+  }
+
+  // height of the cylinder between the two hemispheres
+  // stacks are rounded down to an even number so both hemispheres get the same, at least 2
+  pub fn capsule(radius: f32, height: f32, sectors: u32, stacks: u32) -> Self {
+    let sectors = sectors.max(3);
+    let mut m = Self::new();
+    let rings = stacks.max(2) / 2 * 2;
+    // the equator ring is doubled to stretch the middle
+    for y in 0..=rings + 1 {
+      let (ring, offset) = match y <= rings / 2 {
+        true => (y, -height / 2.0),
+        false => (y - 1, height / 2.0),
+      };
+      let phi = ring as f32 / rings as f32 * PI - PI / 2.0;
+      let pos_y = radius * phi.sin() + offset;
+      let v = (pos_y + radius + height / 2.0) / (height + radius * 2.0);
+      for x in 0..=sectors {
+        let u = x as f32 / sectors as f32;
+        let n = Vec3::new(
+          phi.cos() * (u * TAU).cos(),
+          phi.sin(),
+          -phi.cos() * (u * TAU).sin(),
+        );
+        m.vertex(n * radius + Vec3::Y * offset, Vec2::new(u, v), n);
+      }
+    }
+    m.grid(sectors, rings + 1);
+    m
+  }
+
+  pub fn torus(radius: f32, tube: f32, sectors: u32, sides: u32) -> Self {
+    let (sectors, sides) = (sectors.max(3), sides.max(3));
+    let mut m = Self::new();
+    for y in 0..=sides {
+      let v = y as f32 / sides as f32;
+      for x in 0..=sectors {
+        let u = x as f32 / sectors as f32;
+        let dir = Vec3::new((u * TAU).cos(), 0.0, -(u * TAU).sin());
+        let n = dir * -(v * TAU).cos() + Vec3::Y * -(v * TAU).sin();
+        m.vertex(dir * radius + n * tube, Vec2::new(u, v), n);
+      }
+    }
+    m.grid(sectors, sides);
+    m
+  }
+
+  // quads between rows of `columns + 1` vertices
+  fn grid(&mut self, columns: u32, rows: u32) {
+    for y in 0..rows {
+      for x in 0..columns {
+        let i = y * (columns + 1) + x;
+        self.quad(i, i + 1, i + columns + 2, i + columns + 1);
+      }
+    }
+  }
+
+  fn cap(&mut self, radius: f32, y: f32, sectors: u32, up: bool) {
+    let normal = if up { Vec3::Y } else { Vec3::NEG_Y };
+    let center = self.vertex(Vec3::Y * y, Vec2::splat(0.5), normal);
+    for x in 0..sectors {
+      let [a, b] = [x, x + 1].map(|x| {
+        let angle = x as f32 / sectors as f32 * TAU;
+        let (s, c) = (-angle.sin(), angle.cos());
+        self.vertex(
+          Vec3::new(c * radius, y, s * radius),
+          Vec2::new(0.5 + c / 2.0, 0.5 - s / 2.0),
+          normal,
+        )
+      });
+      if up {
+        self.triangle(center, a, b);
+      } else {
+        self.triangle(center, b, a);
+      }
+    }
+  }
+}
+
+// meshes that can be loaded as "builtin:name"
+pub fn builtin(name: &str) -> Result<MeshBuilder> {
+  Ok(match name {
+    "plane" => MeshBuilder::plane(2.0, 0),
+    "cube" => MeshBuilder::cube(2.0),
+    "sphere" => MeshBuilder::uv_sphere(1.0, 32, 16),
+    "icosphere" => MeshBuilder::ico_sphere(1.0, 2),
+    "cylinder" => MeshBuilder::cylinder(1.0, 2.0, 32),
+    "cone" => MeshBuilder::cone(1.0, 2.0, 32),
+    "capsule" => MeshBuilder::capsule(0.5, 1.0, 32, 16),
+    "torus" => MeshBuilder::torus(0.75, 0.25, 32, 16),
+    _ => return Err(format!("Unknown builtin mesh '{}'.", name).into()),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn check(m: &MeshBuilder, vertices: usize, indices: usize) {
+    assert_eq!(m.positions.len(), vertices);
+    assert_eq!(m.indices.len(), indices);
+    assert!(m.positions.iter().all(|p| p.is_finite()));
+    assert!(m.indices.iter().all(|i| (*i as usize) < vertices));
+  }
+
+  #[test]
+  fn vertex_counts() {
+    check(&MeshBuilder::plane(1.0, 1), 9, 24);
+    check(&MeshBuilder::cube(1.0), 24, 36);
+    check(&MeshBuilder::uv_sphere(1.0, 8, 4), 9 * 5, 8 * 4 * 6);
+    check(
+      &MeshBuilder::cylinder(1.0, 1.0, 8),
+      9 * 2 + 2 * 17,
+      8 * 6 + 2 * 8 * 3,
+    );
+    check(&MeshBuilder::cone(1.0, 1.0, 8), 8 * 3 + 17, 8 * 3 * 2);
+    // 4 stacks and the doubled equator
+    check(&MeshBuilder::capsule(1.0, 1.0, 8, 4), 9 * 6, 8 * 5 * 6);
+    check(&MeshBuilder::torus(1.0, 0.5, 8, 4), 9 * 5, 8 * 4 * 6);
+  }
+
+  #[test]
+  fn degenerate_parameters_are_clamped() {
+    check(&MeshBuilder::plane(1.0, 0), 4, 6);
+    check(&MeshBuilder::uv_sphere(1.0, 0, 0), 4 * 3, 3 * 2 * 6);
+    check(
+      &MeshBuilder::cylinder(1.0, 1.0, 0),
+      4 * 2 + 2 * 7,
+      3 * 6 + 2 * 3 * 3,
+    );
+    check(&MeshBuilder::cone(1.0, 1.0, 1), 3 * 3 + 7, 3 * 3 * 2);
+    check(&MeshBuilder::capsule(1.0, 1.0, 0, 0), 4 * 4, 3 * 3 * 6);
+    check(&MeshBuilder::capsule(1.0, 1.0, 3, 1), 4 * 4, 3 * 3 * 6);
+    check(&MeshBuilder::torus(1.0, 0.5, 0, 0), 4 * 4, 3 * 3 * 6);
+  }
+}
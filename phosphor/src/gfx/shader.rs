@@ -0,0 +1,405 @@
+use std::{fmt, ptr};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::ffi::CString;
+use std::rc::Rc;
+use glam::{Mat4, Vec3};
+use log::trace;
+use serde::Deserialize;
+use shader_prepper::{ResolvedInclude, ResolvedIncludePath};
+use crate::ecs::World;
+use crate::assets::Assets;
+use crate::{Result, asset, vfs};
+use super::{Attribute, track};
+use super::uniform_buffer::block_binding;
+
+struct VfsIncludeProvider;
+impl shader_prepper::IncludeProvider for VfsIncludeProvider {
+  type IncludeContext = ();
+
+  fn resolve_path(
+    &self,
+    path: &str,
+    _: &Self::IncludeContext,
+  ) -> Result<ResolvedInclude<Self::IncludeContext>> {
+    Ok(ResolvedInclude {
+      resolved_path: ResolvedIncludePath(format!("shaders/{}", path)),
+      context: (),
+    })
+  }
+
+  fn get_include(&mut self, resolved: &ResolvedIncludePath) -> Result<String> {
+    vfs::read_to_string(&resolved.0)
+  }
+}
+
+// a compile or link failure, with the driver log mapped back to the files it came from
+#[derive(Debug)]
+pub struct ShaderError {
+  // the shader that failed to compile, or both when linking failed
+  pub path: String,
+  pub link: bool,
+  pub messages: Vec<ShaderMessage>,
+  // as the driver reported it
+  pub log: String,
+}
+
+#[derive(Debug)]
+pub struct ShaderMessage {
+  pub file: String,
+  // None when the driver didnt give one
+  pub line: Option<usize>,
+  pub text: String,
+}
+
+impl fmt::Display for ShaderError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let action = if self.link { "link" } else { "compile" };
+    write!(f, "Failed to {} '{}':", action, self.path)?;
+    for m in self.messages.iter() {
+      match m.line {
+        Some(line) => write!(f, "\n{}:{}: {}", m.file, line, m.text)?,
+        None => write!(f, "\n{}", m.text)?,
+      }
+    }
+    Ok(())
+  }
+}
+
+impl Error for ShaderError {}
+
+// where each included file starts in the combined source
+struct Chunk {
+  start: usize,
+  file: String,
+  line_offset: usize,
+}
+
+impl ShaderError {
+  fn new(path: String, link: bool, log: String, chunks: &[Chunk]) -> Self {
+    let messages = log
+      .lines()
+      .filter(|l| !l.trim().is_empty())
+      .map(|l| match (parse_line(l), chunks.first()) {
+        (Some((line, text)), Some(_)) => {
+          // driver lines start at 1
+          let line = line.saturating_sub(1);
+          let chunk = chunks.iter().rev().find(|c| c.start <= line).unwrap();
+          ShaderMessage {
+            file: chunk.file.clone(),
+            line: Some(chunk.line_offset + line - chunk.start + 1),
+            text: text.to_string(),
+          }
+        }
+        _ => ShaderMessage {
+          file: path.clone(),
+          line: None,
+          text: l.trim().to_string(),
+        },
+      })
+      .collect();
+    Self {
+      path,
+      link,
+      messages,
+      log,
+    }
+  }
+}
+
+// drivers format errors as "0:12(5): error: ..." (mesa), "0(12) : error ..." (nvidia) or "ERROR: 0:12: ..."
+fn parse_line(msg: &str) -> Option<(usize, &str)> {
+  let rest = msg.trim();
+  let rest = ["ERROR: ", "WARNING: "]
+    .iter()
+    .find_map(|p| rest.strip_prefix(p))
+    .unwrap_or(rest);
+  // the source string index, always 0 as the source is passed as one string
+  let rest = rest.strip_prefix('0')?;
+  let rest = rest.strip_prefix(':').or_else(|| rest.strip_prefix('('))?;
+  let end = rest.find(|c: char| !c.is_ascii_digit())?;
+  let line = rest[..end].parse().ok()?;
+  let text = rest[end..].trim_start_matches(|c: char| c.is_ascii_digit() || "():, ".contains(c));
+  Some((line, text))
+}
+
+// returns the shader and the files it was built from
+unsafe fn compile_shader(path: &str, ty: u32, defines: &Defines) -> Result<(u32, Vec<String>)> {
+  trace!("Compiling shader '{}'.", path);
+  let mut src = String::new();
+  let mut chunks = vec![];
+  for c in shader_prepper::process_file(path, &mut VfsIncludeProvider, ())? {
+    chunks.push(Chunk {
+      start: src.matches('\n').count(),
+      file: c.file,
+      line_offset: c.line_offset,
+    });
+    src.push_str(&c.source);
+  }
+  insert_defines(&mut src, &mut chunks, defines);
+  let shader = gl::CreateShader(ty);
+  gl::ShaderSource(
+    shader,
+    1,
+    &(src.as_bytes().as_ptr().cast()),
+    &(src.len().try_into().unwrap()),
+  );
+  gl::CompileShader(shader);
+  let mut success = 0;
+  gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+  if success == 0 {
+    let log = info_log(shader, gl::GetShaderiv, gl::GetShaderInfoLog);
+    gl::DeleteShader(shader);
+    return Err(ShaderError::new(path.to_string(), false, log, &chunks).into());
+  }
+  let mut files: Vec<String> = chunks
+    .into_iter()
+    .filter(|c| c.file != DEFINES)
+    .map(|c| c.file)
+    .collect();
+  files.dedup();
+  Ok((shader, files))
+}
+
+const DEFINES: &str = "<defines>";
+
+// after #version as nothing can come before it, keeping the line mapping of everything after them
+fn insert_defines(src: &mut String, chunks: &mut Vec<Chunk>, defines: &Defines) {
+  if defines.is_empty() {
+    return;
+  }
+  let at = match src.find("#version") {
+    Some(i) => src[i..].find('\n').map_or(src.len(), |n| i + n + 1),
+    None => 0,
+  };
+  let line = src[..at].matches('\n').count();
+  let block: String = defines
+    .iter()
+    .map(|(k, v)| format!("#define {} {}\n", k, v))
+    .collect();
+  src.insert_str(at, &block);
+  let n = defines.len();
+  let split = chunks
+    .iter()
+    .position(|c| c.start >= line)
+    .unwrap_or(chunks.len());
+  let mut inserted = vec![Chunk {
+    start: line,
+    file: DEFINES.to_string(),
+    line_offset: 0,
+  }];
+  // the rest of the chunk the defines were inserted into
+  if let Some(c) = split.checked_sub(1).map(|i| &chunks[i]) {
+    inserted.push(Chunk {
+      start: line + n,
+      file: c.file.clone(),
+      line_offset: c.line_offset + line - c.start,
+    });
+  }
+  for c in chunks[split..].iter_mut() {
+    c.start += n;
+  }
+  chunks.splice(split..split, inserted);
+}
+
+unsafe fn info_log(
+  id: u32,
+  get: unsafe fn(u32, u32, *mut i32),
+  get_log: unsafe fn(u32, i32, *mut i32, *mut gl::types::GLchar),
+) -> String {
+  let mut len = 0;
+  get(id, gl::INFO_LOG_LENGTH, &mut len);
+  let mut log = vec![0u8; len.max(1) as _];
+  get_log(id, len, ptr::null_mut(), log.as_mut_ptr() as _);
+  String::from_utf8_lossy(&log)
+    .trim_end_matches('\0')
+    .to_string()
+}
+
+// a .shader file is json naming the stages, relative to the shaders directory, and optionally defines
+// {"vert": "base.vert", "frag": "default.frag", "defines": {"USE_TEX": "1"}}
+#[derive(Deserialize)]
+struct ShaderDesc {
+  vert: String,
+  frag: String,
+  #[serde(default)]
+  defines: Defines,
+}
+
+// sorted so the same set always finds the same variant
+type Defines = BTreeMap<String, String>;
+
+// recompiled when any of its sources or includes change, keeping the last working program if that fails
+#[asset(load_shader, ext = ["shader"])]
+pub struct Shader {
+  pub id: u32,
+  vert: String,
+  frag: String,
+  defines: Defines,
+  // failures are kept to not recompile every frame
+  variants: RefCell<HashMap<Defines, std::result::Result<Rc<Shader>, String>>>,
+  locations: RefCell<HashMap<String, i32>>,
+}
+
+fn load_shader(world: &mut World, path: &str) -> Result<Shader> {
+  let desc: ShaderDesc = serde_json::from_slice(&vfs::read(path)?)?;
+  let (shader, files) = Shader::build(&desc.vert, &desc.frag, desc.defines)?;
+  let assets = world.get_resource::<Assets>().unwrap();
+  for file in files {
+    assets.add_dependency(path, &file);
+  }
+  Ok(shader)
+}
+
+impl Shader {
+  pub fn new(vert_path: &str, frag_path: &str) -> Result<Self> {
+    Self::with_defines(vert_path, frag_path, &[])
+  }
+
+  pub fn with_defines(vert_path: &str, frag_path: &str, defines: &[(&str, &str)]) -> Result<Self> {
+    Ok(Self::build(vert_path, frag_path, to_defines(defines))?.0)
+  }
+
+  // this shader with more defines, compiled the first time each set is used
+  // variants outlive a reload of this shader, so get them again each frame to pick up changes
+  pub fn variant(&self, defines: &[(&str, &str)]) -> Result<Rc<Shader>> {
+    let mut key = self.defines.clone();
+    key.extend(to_defines(defines));
+    let mut variants = self.variants.borrow_mut();
+    let variant = variants.entry(key).or_insert_with_key(|key| {
+      trace!(
+        "Compiling variant {:?} of '{}' + '{}'.",
+        key,
+        self.vert,
+        self.frag
+      );
+      Self::build(&self.vert, &self.frag, key.clone())
+        .map(|(s, _)| Rc::new(s))
+        .map_err(|e| e.to_string())
+    });
+    match variant {
+      Ok(s) => Ok(s.clone()),
+      Err(e) => Err(e.clone().into()),
+    }
+  }
+
+  fn build(vert_path: &str, frag_path: &str, defines: Defines) -> Result<(Self, Vec<String>)> {
+    unsafe {
+      let (vert, mut files) = compile_shader(vert_path, gl::VERTEX_SHADER, &defines)?;
+      let frag = match compile_shader(frag_path, gl::FRAGMENT_SHADER, &defines) {
+        Ok((frag, frag_files)) => {
+          files.extend(frag_files);
+          frag
+        }
+        Err(e) => {
+          gl::DeleteShader(vert);
+          return Err(e);
+        }
+      };
+      let program = gl::CreateProgram();
+      gl::AttachShader(program, vert);
+      gl::AttachShader(program, frag);
+      // explicit layout qualifiers take precedence over these
+      for a in Attribute::ALL {
+        let name = CString::new(a.name()).unwrap();
+        gl::BindAttribLocation(program, a.location(), name.as_ptr());
+      }
+      gl::LinkProgram(program);
+      // the linked program doesnt need them
+      gl::DetachShader(program, vert);
+      gl::DetachShader(program, frag);
+      gl::DeleteShader(vert);
+      gl::DeleteShader(frag);
+      let mut success = 0;
+      gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
+      if success == 0 {
+        let log = info_log(program, gl::GetProgramiv, gl::GetProgramInfoLog);
+        gl::DeleteProgram(program);
+        let path = format!("{} + {}", vert_path, frag_path);
+        return Err(ShaderError::new(path, true, log, &[]).into());
+      }
+      bind_blocks(program);
+      track(1);
+      let shader = Self {
+        id: program,
+        vert: vert_path.to_string(),
+        frag: frag_path.to_string(),
+        defines,
+        variants: RefCell::new(HashMap::new()),
+        locations: RefCell::new(HashMap::new()),
+      };
+      Ok((shader, files))
+    }
+  }
+
+  pub fn bind(&self) {
+    unsafe { gl::UseProgram(self.id) }
+  }
+
+  fn get_loc(&self, name: &str) -> i32 {
+    if let Some(loc) = self.locations.borrow().get(name) {
+      return *loc;
+    }
+    let c = CString::new(name).unwrap();
+    let loc = unsafe { gl::GetUniformLocation(self.id, c.as_ptr() as _) };
+    self.locations.borrow_mut().insert(name.to_string(), loc);
+    loc
+  }
+
+  pub fn set_mat4(&self, name: &str, val: &Mat4) {
+    unsafe {
+      gl::ProgramUniformMatrix4fv(
+        self.id as _,
+        self.get_loc(name),
+        1,
+        gl::FALSE,
+        val.to_cols_array().as_ptr(),
+      )
+    }
+  }
+
+  pub fn set_vec3(&self, name: &str, val: &Vec3) {
+    unsafe { gl::ProgramUniform3fv(self.id as _, self.get_loc(name), 1, val.to_array().as_ptr()) }
+  }
+
+  pub fn set_i32(&self, name: &str, val: &i32) {
+    unsafe {
+      gl::ProgramUniform1i(self.id as _, self.get_loc(name), *val);
+    }
+  }
+
+  pub fn set_f32(&self, name: &str, val: &f32) {
+    unsafe {
+      gl::ProgramUniform1f(self.id as _, self.get_loc(name), *val);
+    }
+  }
+}
+
+// points each uniform block at the buffer with its name
+unsafe fn bind_blocks(program: u32) {
+  let mut count = 0;
+  gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_BLOCKS, &mut count);
+  for i in 0..count as u32 {
+    let mut len = 0;
+    gl::GetActiveUniformBlockiv(program, i, gl::UNIFORM_BLOCK_NAME_LENGTH, &mut len);
+    let mut name = vec![0u8; len.max(1) as _];
+    gl::GetActiveUniformBlockName(program, i, len, ptr::null_mut(), name.as_mut_ptr() as _);
+    let name = String::from_utf8_lossy(&name);
+    gl::UniformBlockBinding(program, i, block_binding(name.trim_end_matches('\0')));
+  }
+}
+
+fn to_defines(defines: &[(&str, &str)]) -> Defines {
+  defines
+    .iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect()
+}
+
+impl Drop for Shader {
+  fn drop(&mut self) {
+    unsafe { gl::DeleteProgram(self.id) };
+    track(-1);
+  }
+}
@@ -0,0 +1,111 @@
+use std::ptr;
+use super::{Filter, Wrap};
+
+// layers of the same size, eg for shadow cascades
+pub struct TextureArray {
+  pub id: u32,
+  pub width: u32,
+  pub height: u32,
+  pub layers: u32,
+  pub iformat: u32,
+  pub format: u32,
+  pub typ: u32,
+}
+
+impl TextureArray {
+  pub fn new(width: u32, height: u32, layers: u32, iformat: u32, format: u32, typ: u32) -> Self {
+    let mut id = 0;
+    unsafe {
+      gl::GenTextures(1, &mut id);
+    }
+    super::track(1);
+    let mut arr = Self {
+      id,
+      width: 0,
+      height: 0,
+      layers: 0,
+      iformat,
+      format,
+      typ,
+    };
+    arr.resize(width, height, layers);
+    arr.set_filter(Filter::Linear, false);
+    arr.set_wrap(Wrap::Clamp);
+    arr
+  }
+
+  // `data` is one layer in the arrays format and type
+  pub fn set_layer(&self, layer: u32, data: *const u8) {
+    unsafe {
+      gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+      gl::TexSubImage3D(
+        gl::TEXTURE_2D_ARRAY,
+        0,
+        0,
+        0,
+        layer as _,
+        self.width as _,
+        self.height as _,
+        1,
+        self.format,
+        self.typ,
+        data as _,
+      );
+    }
+  }
+
+  pub fn bind(&self, unit: u32) {
+    unsafe {
+      gl::ActiveTexture(gl::TEXTURE0 + unit);
+      gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+    }
+  }
+
+  pub fn set_filter(&self, filter: Filter, mipmaps: bool) {
+    super::set_filter(gl::TEXTURE_2D_ARRAY, self.id, filter, mipmaps);
+  }
+
+  pub fn set_wrap(&self, wrap: Wrap) {
+    super::set_wrap(gl::TEXTURE_2D_ARRAY, self.id, wrap);
+  }
+
+  pub fn set_anisotropy(&self, anisotropy: f32) {
+    super::set_anisotropy(gl::TEXTURE_2D_ARRAY, self.id, anisotropy);
+  }
+
+  pub fn generate_mipmaps(&self) {
+    unsafe {
+      gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+      gl::GenerateMipmap(gl::TEXTURE_2D_ARRAY);
+    }
+  }
+
+  // clears every layer
+  pub fn resize(&mut self, width: u32, height: u32, layers: u32) {
+    unsafe {
+      gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+      gl::TexImage3D(
+        gl::TEXTURE_2D_ARRAY,
+        0,
+        self.iformat as _,
+        width as _,
+        height as _,
+        layers as _,
+        0,
+        self.format,
+        self.typ,
+        ptr::null(),
+      );
+    }
+    self.width = width;
+    self.height = height;
+    self.layers = layers;
+  }
+}
+
+impl Drop for TextureArray {
+  fn drop(&mut self) {
+    unsafe { gl::DeleteTextures(1, &self.id) };
+    super::track(-1);
+  }
+}
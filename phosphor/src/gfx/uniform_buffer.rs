@@ -0,0 +1,76 @@
+use std::{mem, ptr};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+use once_cell::sync::Lazy;
+use super::track;
+
+// binding points are given out by block name, so every shader declaring a block reads the same buffer
+static BINDINGS: Lazy<Mutex<HashMap<String, u32>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub(super) fn block_binding(name: &str) -> u32 {
+  let mut bindings = BINDINGS.lock().unwrap();
+  let next = bindings.len() as u32;
+  *bindings.entry(name.to_string()).or_insert(next)
+}
+
+// data for a glsl uniform block
+/// # Safety
+/// must be repr(C) and match the std140 layout of the block, eg. vec3s padded to 16 bytes
+pub unsafe trait UniformBlock: Copy {}
+
+// a uniform block shared by every shader that declares it, update it once per frame
+pub struct UniformBuffer<T: UniformBlock> {
+  pub id: u32,
+  pub binding: u32,
+  _t: PhantomData<T>,
+}
+
+impl<T: UniformBlock> UniformBuffer<T> {
+  // `name` is the name of the block in glsl
+  pub fn new(name: &str) -> Self {
+    let binding = block_binding(name);
+    let mut id = 0;
+    unsafe {
+      gl::GenBuffers(1, &mut id);
+      gl::BindBuffer(gl::UNIFORM_BUFFER, id);
+      gl::BufferData(
+        gl::UNIFORM_BUFFER,
+        mem::size_of::<T>() as _,
+        ptr::null(),
+        gl::DYNAMIC_DRAW,
+      );
+      gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, id);
+    }
+    track(1);
+    Self {
+      id,
+      binding,
+      _t: PhantomData,
+    }
+  }
+
+  // makes shaders read this buffer, for blocks with more than one, eg. a preview with its own camera
+  pub fn bind(&self) {
+    unsafe { gl::BindBufferBase(gl::UNIFORM_BUFFER, self.binding, self.id) };
+  }
+
+  pub fn update(&self, data: &T) {
+    unsafe {
+      gl::BindBuffer(gl::UNIFORM_BUFFER, self.id);
+      gl::BufferSubData(
+        gl::UNIFORM_BUFFER,
+        0,
+        mem::size_of::<T>() as _,
+        data as *const T as _,
+      );
+    }
+  }
+}
+
+impl<T: UniformBlock> Drop for UniformBuffer<T> {
+  fn drop(&mut self) {
+    unsafe { gl::DeleteBuffers(1, &self.id) };
+    track(-1);
+  }
+}
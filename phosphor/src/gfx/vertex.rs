@@ -0,0 +1,154 @@
+use std::{mem, slice};
+use serde::{Serialize, Deserialize};
+
+// each attribute has a fixed location and name, shaders can use either to bind it
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Attribute {
+  Position,
+  Uv,
+  Normal,
+  // xyz is the tangent, w the sign of the bitangent
+  Tangent,
+  Color,
+  Uv2,
+  // 4 u16 joint indices, read as a uvec4
+  Joints,
+  Weights,
+}
+
+impl Attribute {
+  pub const ALL: [Self; 8] = [
+    Self::Position,
+    Self::Uv,
+    Self::Normal,
+    Self::Tangent,
+    Self::Color,
+    Self::Uv2,
+    Self::Joints,
+    Self::Weights,
+  ];
+
+  pub fn location(self) -> u32 {
+    self as u32
+  }
+
+  // the name of the shader input
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Position => "pos",
+      Self::Uv => "uv",
+      Self::Normal => "normal",
+      Self::Tangent => "tangent",
+      Self::Color => "color",
+      Self::Uv2 => "uv2",
+      Self::Joints => "joints",
+      Self::Weights => "weights",
+    }
+  }
+
+  pub fn components(self) -> usize {
+    match self {
+      Self::Uv | Self::Uv2 => 2,
+      Self::Position | Self::Normal => 3,
+      Self::Tangent | Self::Color | Self::Joints | Self::Weights => 4,
+    }
+  }
+
+  // in bytes
+  pub fn size(self) -> usize {
+    match self {
+      Self::Joints => self.components() * 2,
+      _ => self.components() * 4,
+    }
+  }
+
+  fn gl_type(self) -> u32 {
+    match self {
+      Self::Joints => gl::UNSIGNED_SHORT,
+      _ => gl::FLOAT,
+    }
+  }
+}
+
+// the attributes of an interleaved vertex, in order
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct VertexLayout {
+  pub attributes: Vec<Attribute>,
+}
+
+impl VertexLayout {
+  pub fn new(attributes: &[Attribute]) -> Self {
+    Self {
+      attributes: attributes.to_vec(),
+    }
+  }
+
+  pub fn stride(&self) -> usize {
+    self.attributes.iter().map(|a| a.size()).sum()
+  }
+
+  pub fn offset(&self, attribute: Attribute) -> Option<usize> {
+    let i = self.attributes.iter().position(|a| *a == attribute)?;
+    Some(self.attributes[..i].iter().map(|a| a.size()).sum())
+  }
+
+  pub fn contains(&self, attribute: Attribute) -> bool {
+    self.attributes.contains(&attribute)
+  }
+
+  // sets up the attribute pointers of the bound vao and array buffer
+  pub(crate) fn apply(&self) {
+    let stride = self.stride() as i32;
+    let mut offset = 0;
+    for a in self.attributes.iter() {
+      unsafe {
+        gl::EnableVertexAttribArray(a.location());
+        match a {
+          Attribute::Joints => gl::VertexAttribIPointer(
+            a.location(),
+            a.components() as _,
+            a.gl_type(),
+            stride,
+            offset as _,
+          ),
+          _ => gl::VertexAttribPointer(
+            a.location(),
+            a.components() as _,
+            a.gl_type(),
+            gl::FALSE,
+            stride,
+            offset as _,
+          ),
+        }
+      }
+      offset += a.size();
+    }
+  }
+}
+
+// a vertex type that can be uploaded as is
+/// # Safety
+/// must be repr(C) without padding, with fields matching `ATTRIBUTES`
+pub unsafe trait VertexFormat: Copy {
+  const ATTRIBUTES: &'static [Attribute];
+
+  fn layout() -> VertexLayout {
+    VertexLayout::new(Self::ATTRIBUTES)
+  }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Vertex {
+  pub pos: [f32; 3],
+  pub uv: [f32; 2],
+  pub normal: [f32; 3],
+}
+
+unsafe impl VertexFormat for Vertex {
+  const ATTRIBUTES: &'static [Attribute] = &[Attribute::Position, Attribute::Uv, Attribute::Normal];
+}
+
+pub(crate) fn as_bytes<V: VertexFormat>(vertices: &[V]) -> &[u8] {
+  unsafe { slice::from_raw_parts(vertices.as_ptr() as _, mem::size_of_val(vertices)) }
+}
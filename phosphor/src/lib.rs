@@ -5,15 +5,19 @@
 pub mod gfx;
 pub mod ecs;
 pub mod assets;
+pub mod asset_db;
 pub mod scene;
+pub mod vfs;
 
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::any::{Any, TypeId, type_name};
 use std::cmp::Ordering;
 use std::mem;
+use std::path::Path;
 use glfw::Context;
 use once_cell::unsync::OnceCell;
+use log::{error, warn};
 use crate::gfx::Renderer;
 use crate::ecs::{World, System, stage};
 use crate::assets::Assets;
@@ -54,10 +58,27 @@ impl Engine {
     self
   }
 
+  // see `vfs::mount`, "assets" is mounted if nothing else is
+  pub fn mount<P: AsRef<Path>>(self, path: P) -> Self {
+    if let Err(e) = vfs::mount(path.as_ref()) {
+      error!("Couldnt mount '{}'. {}", path.as_ref().display(), e);
+    }
+    self
+  }
+
+  // for tools that use the world without running the engine
+  pub fn world(&mut self) -> &mut World {
+    unsafe { WORLD.get_mut().unwrap() }
+  }
+
   pub fn run(self) -> Result<()> {
     let world = unsafe { WORLD.get_mut().unwrap() };
+    if !vfs::is_mounted() {
+      vfs::mount("assets")?;
+    }
     world.add_resource(Assets::new());
     world.add_resource(Renderer::new()?);
+    world.add_system(stage::PRE_DRAW, assets::update);
     let renderer = world.get_resource::<Renderer>().unwrap();
     world.run_system(stage::INIT);
     world.run_system(stage::START);
@@ -77,6 +98,16 @@ impl Engine {
       world.run_system(stage::POST_DRAW);
       renderer.window.swap_buffers();
     }
+    // debug builds drop everything while the context is still alive to check for leaked gl objects
+    if cfg!(debug_assertions) {
+      let renderer = world.resources.remove(&TypeIdNamed::of::<Renderer>());
+      world.components.clear();
+      world.resources.clear();
+      if gfx::live_objects() > 0 {
+        warn!("Leaked {} gl objects.", gfx::live_objects());
+      }
+      drop(renderer);
+    }
     Ok(())
   }
 }
@@ -111,7 +142,8 @@ impl TypeIdNamed {
   }
 
   pub fn id(&self) -> usize {
-    unsafe { mem::transmute(self.id) }
+    // the first 8 bytes, which is all of a TypeId on older toolchains
+    unsafe { mem::transmute_copy(&self.id) }
   }
 }
 
@@ -1,69 +1,444 @@
-use std::collections::HashMap;
-use std::fs::File;
+use std::collections::{HashMap, BTreeMap};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
 use std::any::Any;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
 use linkme::distributed_slice;
-use log::{info, warn, trace};
-use crate::ecs::World;
-use crate::assets::Assets;
+use log::{info, warn, error, trace};
+use crate::ecs::{World, Entity};
+use crate::assets;
 use crate::{TypeIdNamed, Result, HashMapExt};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Scene {
+  pub entities: HashMap<usize, Vec<(usize, Vec<u8>)>>,
+  pub resources: HashMap<usize, Vec<u8>>,
+}
+
+// binary scenes start with the magic and version, bump the version when the layout of Scene changes
+const MAGIC: &[u8; 8] = b"PHOSCENE";
+pub const SCENE_VERSION: u32 = 1;
+
+// the layout from before resources were saved, those scenes have no header either
+#[derive(Deserialize)]
+struct SceneV0 {
   entities: HashMap<usize, Vec<(usize, Vec<u8>)>>,
 }
 
+// human readable scene, types are keyed by name instead of id
+#[derive(PartialEq, Serialize, Deserialize)]
+pub struct SceneText {
+  pub entities: BTreeMap<usize, Vec<(String, Value)>>,
+  pub resources: BTreeMap<String, Value>,
+}
+
+pub type SaveFn = fn(&Box<dyn Any>, &World) -> Result<Vec<u8>>;
+pub type LoadFn = fn(&[u8], &World) -> Result<Box<dyn Any>>;
+// puts saved data back into an existing component, eg. to keep its rapier body
+pub type RestoreFn = fn(&mut Box<dyn Any>, &[u8], &World) -> Result;
+
 pub struct Loader {
   pub id: TypeIdNamed,
-  pub save: fn(&Box<dyn Any>) -> Vec<u8>,
-  pub load: fn(Vec<u8>, &mut Assets) -> Box<dyn Any>,
+  pub save: SaveFn,
+  pub load: LoadFn,
+  // when None, restoring a scene replaces the component with a loaded one
+  pub restore: Option<RestoreFn>,
+  pub to_json: fn(&[u8]) -> Result<Value>,
+  pub from_json: fn(Value) -> Result<Vec<u8>>,
+}
+
+// handles are converted as references, their assets arent loaded
+pub fn to_json<T: Serialize + DeserializeOwned>(data: &[u8]) -> Result<Value> {
+  assets::without_loading(|| Ok(serde_json::to_value(bincode::deserialize::<T>(data)?)?))
+}
+
+pub fn from_json<T: Serialize + DeserializeOwned>(value: Value) -> Result<Vec<u8>> {
+  assets::without_loading(|| Ok(bincode::serialize(&serde_json::from_value::<T>(value)?)?))
 }
 
 #[distributed_slice]
 pub static COMPONENT_LOADERS: [Loader] = [..];
 
-impl Scene {
-  pub fn save(world: &World, path: PathBuf) -> Result {
+#[distributed_slice]
+pub static RESOURCE_LOADERS: [Loader] = [..];
+
+impl World {
+  pub fn snapshot(&self) -> Scene {
     let mut scene = Scene {
-      entities: HashMap::new(),
+      entities: self.save_entities(|_| true),
+      resources: HashMap::new(),
     };
-    for (t, v) in world.components.iter() {
+    for (t, r) in self.resources.iter() {
+      if let Some(loader) = RESOURCE_LOADERS.iter().find(|l| l.id == *t) {
+        trace!("Saving resource '{}'.", t.name);
+        match (loader.save)(r, self) {
+          Ok(d) => {
+            scene.resources.insert(t.id(), d);
+          }
+          Err(e) => error!("Couldn't save resource '{}'. {}", t.name, e),
+        }
+      }
+    }
+    scene
+  }
+
+  // replaces the components that scenes save, other components are kept
+  pub fn restore(&mut self, scene: &Scene) {
+    // how many components of a type on an entity were restored in place, they take the first of its saved ones
+    let mut restored: HashMap<(usize, usize), usize> = HashMap::new();
+    for loader in COMPONENT_LOADERS.iter() {
+      let Some(mut v) = self.components.remove(&loader.id) else {
+        continue;
+      };
+      let t = loader.id.id();
+      v.retain_mut(|(e, c)| {
+        let n = restored.entry((*e, t)).or_default();
+        let data = scene
+          .entities
+          .get(e)
+          .and_then(|cs| cs.iter().filter(|(ct, _)| *ct == t).nth(*n));
+        let (Some(restore), Some((_, d))) = (loader.restore, data) else {
+          return false;
+        };
+        match restore(c, d, self) {
+          Ok(()) => {
+            *n += 1;
+            true
+          }
+          Err(err) => {
+            error!("Couldn't restore '{}' on {}. {}", loader.id.name, e, err);
+            false
+          }
+        }
+      });
+      if !v.is_empty() {
+        self.components.insert(loader.id, v);
+      }
+    }
+    for (id, v) in scene.entities.iter() {
+      let mut skip: HashMap<usize, usize> = HashMap::new();
+      self.load_entity(
+        *id,
+        v.iter().filter(|(t, _)| {
+          let n = skip.entry(*t).or_default();
+          *n += 1;
+          *n > restored.get(&(*id, *t)).copied().unwrap_or(0)
+        }),
+      );
+    }
+    for (t, d) in scene.resources.iter() {
+      if let Some(loader) = RESOURCE_LOADERS.iter().find(|l| l.id.id() == *t) {
+        trace!("Loading resource '{}'.", loader.id.name);
+        match (loader.load)(d, self) {
+          Ok(r) => {
+            self.resources.insert(loader.id, r);
+          }
+          Err(e) => error!("Couldn't load resource '{}'. {}", loader.id.name, e),
+        }
+      }
+    }
+  }
+
+  // scene containing only the given entities, without resources
+  pub fn copy(&self, entities: &[Entity]) -> Scene {
+    Scene {
+      entities: self.save_entities(|i| entities.iter().any(|e| e.id == i)),
+      resources: HashMap::new(),
+    }
+  }
+
+  // spawns the scene's entities under new ids
+  pub fn paste(&self, scene: &Scene) -> Vec<Entity> {
+    scene
+      .entities
+      .values()
+      .map(|v| {
+        let e = self.spawn_empty();
+        self.load_entity(e.id, v.iter());
+        e
+      })
+      .collect()
+  }
+
+  pub fn clone_entity(&self, e: Entity) -> Entity {
+    self
+      .paste(&self.copy(&[e]))
+      .pop()
+      .unwrap_or_else(|| self.spawn_empty())
+  }
+
+  fn save_entities<F: Fn(usize) -> bool>(&self, f: F) -> HashMap<usize, Vec<(usize, Vec<u8>)>> {
+    let mut entities = HashMap::new();
+    for (t, v) in self.components.iter() {
       if let Some(loader) = COMPONENT_LOADERS.iter().find(|l| l.id == *t) {
-        for (i, d) in v {
+        for (i, d) in v.iter().filter(|(i, _)| f(*i)) {
           trace!("Saving '{}' on {}.", t.name, i);
-          scene
-            .entities
-            .push_or_insert(*i, (t.id(), (loader.save)(d)));
+          match (loader.save)(d, self) {
+            Ok(d) => entities.push_or_insert(*i, (t.id(), d)),
+            Err(e) => error!("Couldn't save '{}' on {}. {}", t.name, i, e),
+          }
         }
       } else {
         warn!("{} cannot be serialized.", t.name);
       }
     }
-    bincode::serialize_into(File::create(path.clone())?, &scene)?;
+    entities
+  }
+
+  fn load_entity<'a>(&self, id: usize, components: impl Iterator<Item = &'a (usize, Vec<u8>)>) {
+    for (t, d) in components {
+      if let Some(loader) = COMPONENT_LOADERS.iter().find(|l| l.id.id() == *t) {
+        trace!("Loading '{}' on {}.", loader.id.name, id);
+        match (loader.load)(d, self) {
+          Ok(c) => Entity { id }.insert_boxed(loader.id, c),
+          Err(e) => error!("Couldn't load '{}' on {}. {}", loader.id.name, id, e),
+        }
+      }
+    }
+  }
+}
+
+impl Scene {
+  pub fn save(world: &World, path: PathBuf) -> Result {
+    world.snapshot().write(&path)?;
     info!("Saved scene to '{}'.", path.display());
     Ok(())
   }
 
   pub fn load(world: &mut World, path: PathBuf) -> Result {
-    let scene: Scene = bincode::deserialize_from(File::open(path.clone())?)?;
-    world.components.clear();
-    for (_, v) in scene.entities.iter() {
-      let id = world.spawn_empty().id;
+    world.restore(&Scene::read(&path)?);
+    info!("Loaded scene from '{}'.", path.display());
+    Ok(())
+  }
+
+  // .json files are read as text, anything else as bincode
+  pub fn read(path: &Path) -> Result<Self> {
+    if is_text(path) {
+      Self::from_text(serde_json::from_str(&fs::read_to_string(path)?)?)
+    } else {
+      Self::from_bytes(&fs::read(path)?)
+    }
+  }
+
+  pub fn from_bytes(data: &[u8]) -> Result<Self> {
+    let Some(rest) = data.strip_prefix(MAGIC) else {
+      // no header, with or without resources
+      return bincode::deserialize(data).or_else(|_| {
+        let old: SceneV0 = bincode::deserialize(data)?;
+        Ok(Scene {
+          entities: old.entities,
+          resources: HashMap::new(),
+        })
+      });
+    };
+    let (version, rest) = rest.split_at(4.min(rest.len()));
+    match version.try_into().map(u32::from_le_bytes) {
+      Ok(SCENE_VERSION) => Ok(bincode::deserialize(rest)?),
+      Ok(v) => Err(
+        format!(
+          "Unsupported scene version {}, expected {}.",
+          v, SCENE_VERSION
+        )
+        .into(),
+      ),
+      Err(_) => Err("Scene header is cut off.".into()),
+    }
+  }
+
+  pub fn to_bytes(&self) -> Result<Vec<u8>> {
+    let mut data = MAGIC.to_vec();
+    data.extend(SCENE_VERSION.to_le_bytes());
+    bincode::serialize_into(&mut data, self)?;
+    Ok(data)
+  }
+
+  pub fn write(&self, path: &Path) -> Result {
+    let mut f = BufWriter::new(File::create(path)?);
+    if is_text(path) {
+      serde_json::to_writer_pretty(f, &self.to_text()?)?;
+    } else {
+      f.write_all(&self.to_bytes()?)?;
+    }
+    Ok(())
+  }
+
+  // json text for the system clipboard
+  pub fn to_clipboard(&self) -> Result<String> {
+    Ok(serde_json::to_string(&self.to_text()?)?)
+  }
+
+  pub fn from_clipboard(s: &str) -> Result<Self> {
+    Self::from_text(serde_json::from_str(s)?)
+  }
+
+  pub fn to_text(&self) -> Result<SceneText> {
+    let mut text = SceneText {
+      entities: BTreeMap::new(),
+      resources: BTreeMap::new(),
+    };
+    for (id, v) in self.entities.iter() {
+      let mut components = vec![];
       for (t, d) in v {
-        if let Some(loader) = COMPONENT_LOADERS.iter().find(|l| l.id.id() == *t) {
-          trace!("Loading '{}' on {}.", loader.id.name, id);
-          world.components.push_or_insert(
-            loader.id,
-            (
-              id,
-              (loader.load)(d.clone(), world.get_resource::<Assets>().unwrap()),
-            ),
-          )
+        let loader = find_id(&COMPONENT_LOADERS, *t)?;
+        components.push((loader.id.name.to_string(), (loader.to_json)(d)?));
+      }
+      text.entities.insert(*id, components);
+    }
+    for (t, d) in self.resources.iter() {
+      let loader = find_id(&RESOURCE_LOADERS, *t)?;
+      text
+        .resources
+        .insert(loader.id.name.to_string(), (loader.to_json)(d)?);
+    }
+    Ok(text)
+  }
+
+  // fails with every bad component and resource, one per line
+  pub fn from_text(text: SceneText) -> Result<Self> {
+    let mut scene = Scene {
+      entities: HashMap::new(),
+      resources: HashMap::new(),
+    };
+    let mut errors = vec![];
+    for (id, v) in text.entities {
+      let mut components = vec![];
+      for (name, d) in v {
+        match find_name(&COMPONENT_LOADERS, &name).and_then(|l| Ok((l.id.id(), (l.from_json)(d)?)))
+        {
+          Ok(c) => components.push(c),
+          Err(e) => errors.push(format!("entity {}: '{}': {}", id, name, e)),
         }
       }
+      scene.entities.insert(id, components);
     }
-    info!("Loaded scene from '{}'.", path.display());
-    Ok(())
+    for (name, d) in text.resources {
+      match find_name(&RESOURCE_LOADERS, &name).and_then(|l| Ok((l.id.id(), (l.from_json)(d)?))) {
+        Ok((t, d)) => {
+          scene.resources.insert(t, d);
+        }
+        Err(e) => errors.push(format!("resource '{}': {}", name, e)),
+      }
+    }
+    if !errors.is_empty() {
+      return Err(errors.join("\n").into());
+    }
+    Ok(scene)
+  }
+}
+
+fn is_text(path: &Path) -> bool {
+  path.extension().and_then(|e| e.to_str()) == Some("json")
+}
+
+fn find_id(loaders: &'static [Loader], id: usize) -> Result<&'static Loader> {
+  loaders
+    .iter()
+    .find(|l| l.id.id() == id)
+    .ok_or_else(|| format!("Unknown type {:016x}.", id).into())
+}
+
+fn find_name(loaders: &'static [Loader], name: &str) -> Result<&'static Loader> {
+  loaders
+    .iter()
+    .find(|l| l.id.name == name)
+    .ok_or_else(|| format!("Unknown type '{}'.", name).into())
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+  use super::*;
+  use crate::Engine;
+  use crate::ecs::Name;
+
+  // not saved with scenes
+  struct Marker;
+
+  #[test]
+  fn restore_replaces_saved_components_only() {
+    let mut engine = Engine::new();
+    let world = engine.world();
+    let a = world.spawn("a").insert(Marker);
+    let scene = world.snapshot();
+    a.get_one::<Name>().unwrap().0 = "changed".to_string();
+    let b = world.spawn("b");
+    world.restore(&scene);
+    assert_eq!(a.get_one::<Name>().unwrap().0, "a");
+    assert!(a.get_one::<Marker>().is_some());
+    assert!(b.get_one::<Name>().is_none());
+    assert!(world.snapshot().entities == scene.entities);
+  }
+
+  #[test]
+  fn text_round_trips() {
+    let name = TypeIdNamed::of::<Name>();
+    let mut scene = Scene {
+      entities: HashMap::new(),
+      resources: HashMap::new(),
+    };
+    let name_data = bincode::serialize(&Name("a".to_string())).unwrap();
+    scene.entities.insert(1, vec![(name.id(), name_data)]);
+    let text = scene.to_text().unwrap();
+    assert_eq!(text.entities[&1], vec![(name.name.to_string(), json!("a"))]);
+    assert!(Scene::from_text(text).unwrap().entities == scene.entities);
+  }
+
+  #[test]
+  fn text_errors_are_all_reported() {
+    let text = SceneText {
+      entities: BTreeMap::from([(
+        1,
+        vec![
+          (TypeIdNamed::of::<Name>().name.to_string(), json!(5)),
+          ("Missing".to_string(), json!(null)),
+        ],
+      )]),
+      resources: BTreeMap::from([("MissingResource".to_string(), json!(null))]),
+    };
+    let e = Scene::from_text(text).err().unwrap().to_string();
+    let lines: Vec<_> = e.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", e);
+    assert!(lines[0].starts_with("entity 1: 'phosphor::ecs::Name'"));
+    assert_eq!(lines[1], "entity 1: 'Missing': Unknown type 'Missing'.");
+    assert_eq!(
+      lines[2],
+      "resource 'MissingResource': Unknown type 'MissingResource'."
+    );
+  }
+
+  #[test]
+  fn binary_scenes_have_a_version() {
+    let name = TypeIdNamed::of::<Name>();
+    let scene = Scene {
+      entities: HashMap::from([(1, vec![(name.id(), vec![1, 2])])]),
+      resources: HashMap::from([(2, vec![3])]),
+    };
+    let data = scene.to_bytes().unwrap();
+    assert!(data.starts_with(MAGIC));
+    let read = Scene::from_bytes(&data).unwrap();
+    assert!(read.entities == scene.entities && read.resources == scene.resources);
+
+    let mut newer = data.clone();
+    newer[MAGIC.len()] += 1;
+    assert!(Scene::from_bytes(&newer).is_err());
+    assert!(Scene::from_bytes(&data[..MAGIC.len() + 2]).is_err());
+  }
+
+  #[test]
+  fn headerless_scenes_still_load() {
+    let entities = HashMap::from([(1, vec![(2, vec![3])])]);
+    let without_resources = bincode::serialize(&entities).unwrap();
+    let read = Scene::from_bytes(&without_resources).unwrap();
+    assert!(read.entities == entities && read.resources.is_empty());
+
+    let scene = Scene {
+      entities,
+      resources: HashMap::from([(4, vec![5])]),
+    };
+    let read = Scene::from_bytes(&bincode::serialize(&scene).unwrap()).unwrap();
+    assert!(read.resources == scene.resources);
   }
 }
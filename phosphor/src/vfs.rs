@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Write, Seek, SeekFrom, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::UNIX_EPOCH;
+use log::{debug, trace};
+use crate::Result;
+use crate::asset_db;
+use crate::assets::COOKED_DIR;
+
+const MAGIC: &[u8; 8] = b"PHOSPAK1";
+
+enum Mount {
+  Dir(PathBuf),
+  Archive {
+    path: PathBuf,
+    // name -> (offset, len), offsets are from the end of the index
+    index: HashMap<String, (u64, u64)>,
+    data_start: u64,
+  },
+}
+
+static MOUNTS: RwLock<Vec<Mount>> = RwLock::new(Vec::new());
+
+// mounts a directory of loose files or a packed archive, later mounts take priority
+pub fn mount<P: AsRef<Path>>(path: P) -> Result {
+  let path = path.as_ref();
+  let mount = if path.is_dir() {
+    debug!("Mounted directory '{}'.", path.display());
+    Mount::Dir(fs::canonicalize(path)?)
+  } else {
+    let mut f = File::open(path)?;
+    let mut magic = [0; 8];
+    f.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+      return Err(format!("'{}' is not an archive.", path.display()).into());
+    }
+    let mut len = [0; 8];
+    f.read_exact(&mut len)?;
+    let len = u64::from_le_bytes(len);
+    let mut index = vec![0; len as _];
+    f.read_exact(&mut index)?;
+    let index: HashMap<String, (u64, u64)> = bincode::deserialize(&index)?;
+    debug!(
+      "Mounted archive '{}' with {} files.",
+      path.display(),
+      index.len()
+    );
+    Mount::Archive {
+      path: path.to_path_buf(),
+      index,
+      data_start: 16 + len,
+    }
+  };
+  MOUNTS.write().unwrap().push(mount);
+  Ok(())
+}
+
+pub fn is_mounted() -> bool {
+  !MOUNTS.read().unwrap().is_empty()
+}
+
+pub fn read(path: &str) -> Result<Vec<u8>> {
+  for mount in MOUNTS.read().unwrap().iter().rev() {
+    match mount {
+      Mount::Dir(dir) => {
+        let p = dir.join(path);
+        if p.is_file() {
+          trace!("Reading '{}'.", p.display());
+          return Ok(fs::read(p)?);
+        }
+      }
+      Mount::Archive {
+        path: archive,
+        index,
+        data_start,
+      } => {
+        if let Some((offset, len)) = index.get(path) {
+          trace!("Reading '{}' from '{}'.", path, archive.display());
+          let mut f = File::open(archive)?;
+          f.seek(SeekFrom::Start(data_start + offset))?;
+          let mut data = vec![0; *len as _];
+          f.read_exact(&mut data)?;
+          return Ok(data);
+        }
+      }
+    }
+  }
+  Err(format!("Couldnt find '{}'.", path).into())
+}
+
+pub fn read_to_string(path: &str) -> Result<String> {
+  Ok(String::from_utf8(read(path)?)?)
+}
+
+// size and modification time in ms since the epoch, archives only keep the size
+pub fn stat(path: &str) -> Result<(u64, Option<u64>)> {
+  for mount in MOUNTS.read().unwrap().iter().rev() {
+    match mount {
+      Mount::Dir(dir) => {
+        let p = dir.join(path);
+        if p.is_file() {
+          let m = fs::metadata(p)?;
+          let mtime = m.modified()?.duration_since(UNIX_EPOCH)?.as_millis();
+          return Ok((m.len(), Some(mtime as _)));
+        }
+      }
+      Mount::Archive { index, .. } => {
+        if let Some((_, len)) = index.get(path) {
+          return Ok((*len, None));
+        }
+      }
+    }
+  }
+  Err(format!("Couldnt find '{}'.", path).into())
+}
+
+pub fn exists(path: &str) -> bool {
+  MOUNTS.read().unwrap().iter().any(|m| match m {
+    Mount::Dir(dir) => dir.join(path).is_file(),
+    Mount::Archive { index, .. } => index.contains_key(path),
+  })
+}
+
+// the mounted directory a loose file is read from
+pub fn dir_of(path: &str) -> Option<PathBuf> {
+  for mount in MOUNTS.read().unwrap().iter().rev() {
+    match mount {
+      Mount::Dir(dir) if dir.join(path).is_file() => return Some(dir.clone()),
+      Mount::Archive { index, .. } if index.contains_key(path) => return None,
+      _ => {}
+    }
+  }
+  None
+}
+
+// every file in every mount, except cooked files which are found through their source
+pub fn files() -> Result<Vec<String>> {
+  let mut names = vec![];
+  for mount in MOUNTS.read().unwrap().iter() {
+    match mount {
+      Mount::Dir(dir) => {
+        let mut files = vec![];
+        walk(dir, dir, &mut files)?;
+        names.extend(files.into_iter().map(|(name, _)| name));
+      }
+      Mount::Archive { index, .. } => names.extend(index.keys().cloned()),
+    }
+  }
+  names.retain(|n| !n.starts_with(COOKED_DIR));
+  names.sort();
+  names.dedup();
+  Ok(names)
+}
+
+// mounted directories, for watching loose files
+pub fn dirs() -> Vec<PathBuf> {
+  MOUNTS
+    .read()
+    .unwrap()
+    .iter()
+    .filter_map(|m| match m {
+      Mount::Dir(dir) => Some(dir.clone()),
+      Mount::Archive { .. } => None,
+    })
+    .collect()
+}
+
+// packs every file under `dir` into an archive that can be mounted, cooked files included
+// .meta files that only give their asset a guid are left out, references fall back to the path
+// those with import settings are kept as loading reads them
+pub fn pack<P: AsRef<Path>, Q: AsRef<Path>>(dir: P, out: Q) -> Result {
+  let mut files = vec![];
+  walk(dir.as_ref(), dir.as_ref(), &mut files)?;
+  files.retain(|(name, p)| {
+    !name.ends_with(".meta") || !fs::read(p).is_ok_and(|d| asset_db::only_guid(&d))
+  });
+  let mut index = HashMap::new();
+  let mut offset = 0;
+  for (name, p) in files.iter() {
+    let len = fs::metadata(p)?.len();
+    index.insert(name.clone(), (offset, len));
+    offset += len;
+  }
+  let index = bincode::serialize(&index)?;
+  let mut f = BufWriter::new(File::create(out.as_ref())?);
+  f.write_all(MAGIC)?;
+  f.write_all(&(index.len() as u64).to_le_bytes())?;
+  f.write_all(&index)?;
+  for (_, p) in files {
+    f.write_all(&fs::read(p)?)?;
+  }
+  f.flush()?;
+  Ok(())
+}
+
+fn walk(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> Result {
+  for entry in fs::read_dir(dir)? {
+    let p = entry?.path();
+    if p.is_dir() {
+      walk(root, &p, files)?;
+    } else {
+      let name = p.strip_prefix(root)?.to_string_lossy().replace('\\', "/");
+      files.push((name, p));
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,239 @@
+use std::path::Path;
+use ::gltf::buffer::Source as BufferSource;
+use ::gltf::image::Source as ImageSource;
+use ::gltf::texture::{MagFilter, MinFilter, Sampler, WrappingMode};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use phosphor::{Result, asset, vfs};
+use phosphor::ecs::{World, Entity};
+use phosphor::gfx::{Filter, Mesh, MeshBuilder, Texture, TextureSettings, Wrap};
+use phosphor::assets::{Assets, Handle};
+use phosphor::math::{Mat4, Vec2, Vec3, Vec4};
+use crate::{Transform, Model, Material};
+
+// the contents of a gltf or glb, meshes and textures are added as "file.glb#mesh0/1" and "file.glb#tex0"
+#[asset(load_gltf, ext = ["gltf", "glb"])]
+pub struct Gltf {
+  // primitives of each mesh
+  pub meshes: Vec<Vec<Primitive>>,
+  pub materials: Vec<Material>,
+  pub textures: Vec<Handle<Texture>>,
+  pub nodes: Vec<Node>,
+  // nodes of the default scene
+  pub roots: Vec<usize>,
+}
+
+pub struct Primitive {
+  pub mesh: Handle<Mesh>,
+  pub material: Option<usize>,
+}
+
+pub struct Node {
+  pub name: String,
+  // relative to the parent
+  pub transform: Mat4,
+  pub mesh: Option<usize>,
+  pub children: Vec<usize>,
+}
+
+fn load_gltf(world: &mut World, path: &str) -> Result<Gltf> {
+  let assets = world.get_resource::<Assets>().unwrap();
+  let gltf = ::gltf::Gltf::from_slice(&vfs::read(path)?)?;
+  let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+  let relative = |uri: &str| dir.join(uri).to_string_lossy().replace('\\', "/");
+
+  let mut buffers = vec![];
+  for buffer in gltf.buffers() {
+    buffers.push(match buffer.source() {
+      BufferSource::Bin => gltf.blob.clone().ok_or("Missing glb binary chunk.")?,
+      BufferSource::Uri(uri) if uri.starts_with("data:") => decode_data_uri(uri)?,
+      BufferSource::Uri(uri) => vfs::read(&relative(uri))?,
+    });
+  }
+
+  let mut images = vec![];
+  for image in gltf.images() {
+    images.push(match image.source() {
+      ImageSource::View { view, .. } => {
+        let buffer = &buffers[view.buffer().index()];
+        buffer[view.offset()..view.offset() + view.length()].to_vec()
+      }
+      ImageSource::Uri { uri, .. } if uri.starts_with("data:") => decode_data_uri(uri)?,
+      ImageSource::Uri { uri, .. } => vfs::read(&relative(uri))?,
+    });
+  }
+
+  // one texture per gltf texture as each has its own sampler, the .meta of external images is ignored
+  let mut textures = vec![];
+  for texture in gltf.textures() {
+    let settings = sampler_settings(&texture.sampler());
+    let tex = Texture::from_memory(&images[texture.source().index()], settings)?;
+    textures.push(assets.add(&format!("{}#tex{}", path, texture.index()), tex));
+  }
+
+  let materials = gltf
+    .materials()
+    .map(|m| {
+      let pbr = m.pbr_metallic_roughness();
+      let [r, g, b, _] = pbr.base_color_factor();
+      Material {
+        color: Vec3::new(r, g, b),
+        tex: pbr
+          .base_color_texture()
+          .map(|t| textures[t.texture().index()].clone()),
+        spec: 1.0 - pbr.roughness_factor(),
+        metallic: pbr.metallic_factor(),
+      }
+    })
+    .collect();
+
+  let mut meshes = vec![];
+  for mesh in gltf.meshes() {
+    let mut primitives = vec![];
+    for primitive in mesh.primitives() {
+      let reader = primitive.reader(|b| buffers.get(b.index()).map(Vec::as_slice));
+      let mut builder = MeshBuilder {
+        positions: reader
+          .read_positions()
+          .ok_or("Primitive has no positions.")?
+          .map(Vec3::from)
+          .collect(),
+        ..Default::default()
+      };
+      let n = builder.positions.len();
+      // missing uvs and normals are zeroed, the other attributes are only kept when present
+      builder.uvs = match reader.read_tex_coords(0) {
+        Some(t) => t.into_f32().map(Vec2::from).collect(),
+        None => vec![Vec2::ZERO; n],
+      };
+      builder.normals = match reader.read_normals() {
+        Some(n) => n.map(Vec3::from).collect(),
+        None => vec![Vec3::ZERO; n],
+      };
+      if let Some(t) = reader.read_tangents() {
+        builder.tangents = t.map(Vec4::from).collect();
+      }
+      if let Some(c) = reader.read_colors(0) {
+        builder.colors = c.into_rgba_f32().map(Vec4::from).collect();
+      }
+      if let Some(t) = reader.read_tex_coords(1) {
+        builder.uvs2 = t.into_f32().map(Vec2::from).collect();
+      }
+      if let Some(j) = reader.read_joints(0) {
+        builder.joints = j.into_u16().collect();
+      }
+      if let Some(w) = reader.read_weights(0) {
+        builder.weights = w.into_f32().map(Vec4::from).collect();
+      }
+      builder.indices = match reader.read_indices() {
+        Some(i) => i.into_u32().collect(),
+        None => (0..n as u32).collect(),
+      };
+      primitives.push(Primitive {
+        mesh: assets.add(
+          &format!("{}#mesh{}/{}", path, mesh.index(), primitive.index()),
+          builder.build(),
+        ),
+        material: primitive.material().index(),
+      });
+    }
+    meshes.push(primitives);
+  }
+
+  let nodes = gltf
+    .nodes()
+    .map(|n| Node {
+      name: n
+        .name()
+        .map_or_else(|| format!("node{}", n.index()), str::to_string),
+      transform: Mat4::from_cols_array_2d(&n.transform().matrix()),
+      mesh: n.mesh().map(|m| m.index()),
+      children: n.children().map(|c| c.index()).collect(),
+    })
+    .collect();
+  let roots = match gltf.default_scene().or_else(|| gltf.scenes().next()) {
+    Some(scene) => scene.nodes().map(|n| n.index()).collect(),
+    None => vec![],
+  };
+
+  Ok(Gltf {
+    meshes,
+    materials,
+    textures,
+    nodes,
+    roots,
+  })
+}
+
+// gltf uvs start at the top left so images arent flipped, and wrapping defaults to repeat
+fn sampler_settings(sampler: &Sampler) -> TextureSettings {
+  let wrap = |w| match w {
+    WrappingMode::ClampToEdge => Wrap::Clamp,
+    WrappingMode::MirroredRepeat => Wrap::Mirror,
+    WrappingMode::Repeat => Wrap::Repeat,
+  };
+  // theres only one wrap mode for both axes, t is ignored
+  let mut settings = TextureSettings {
+    wrap: wrap(sampler.wrap_s()),
+    flip: false,
+    ..Default::default()
+  };
+  if let Some(MagFilter::Nearest) = sampler.mag_filter() {
+    settings.filter = Filter::Nearest;
+  }
+  settings.mipmaps = !matches!(
+    sampler.min_filter(),
+    Some(MinFilter::Nearest | MinFilter::Linear)
+  );
+  settings
+}
+
+// eg. "data:application/octet-stream;base64,AAAB..."
+fn decode_data_uri(uri: &str) -> Result<Vec<u8>> {
+  let (header, data) = uri.split_once(',').ok_or("Invalid data uri.")?;
+  if !header.ends_with(";base64") {
+    return Err("Only base64 data uris are supported.".into());
+  }
+  Ok(STANDARD.decode(data)?)
+}
+
+impl Gltf {
+  // instantiates the node hierarchy as an entity with Transform, Model and Material for each primitive
+  // the world has no parent links, so each entity gets the world transform of its node and nodes without
+  // a mesh spawn nothing, walk nodes and roots when the hierarchy itself is needed
+  pub fn spawn(&self, world: &World, transform: Mat4) -> Vec<Entity> {
+    let mut entities = vec![];
+    for root in self.roots.iter() {
+      self.spawn_node(world, *root, transform, &mut entities);
+    }
+    entities
+  }
+
+  fn spawn_node(&self, world: &World, node: usize, parent: Mat4, entities: &mut Vec<Entity>) {
+    let node = &self.nodes[node];
+    let transform = parent * node.transform;
+    if let Some(mesh) = node.mesh {
+      let (scale, rotation, position) = transform.to_scale_rotation_translation();
+      for primitive in self.meshes[mesh].iter() {
+        let material = match primitive.material {
+          Some(i) => self.materials[i].clone(),
+          None => Material::DEFAULT,
+        };
+        entities.push(
+          world
+            .spawn(&node.name)
+            .insert(Transform {
+              position,
+              rotation,
+              scale,
+            })
+            .insert(Model::new(primitive.mesh.clone()))
+            .insert(material),
+        );
+      }
+    }
+    for child in node.children.iter() {
+      self.spawn_node(world, *child, transform, entities);
+    }
+  }
+}
@@ -1,16 +1,24 @@
 #![allow(clippy::new_without_default)]
-use std::ptr;
+pub mod gltf;
+pub mod render_graph;
+
+use std::rc::Rc;
 use phosphor::Result;
-use phosphor::gfx::{Renderer, Shader, Texture, Mesh, Framebuffer, Vertex, Query, gl};
+use phosphor::gfx::{
+  Renderer, Shader, Texture, Mesh, MeshMaterial, Framebuffer, Vertex, UniformBuffer, UniformBlock,
+  gl,
+};
 use phosphor::ecs::{World, Name, stage};
 use phosphor::math::{Vec3, Quat, Mat4, Vec2, EulerRot};
-use phosphor::assets::Handle;
-use phosphor::component;
+use phosphor::assets::{Assets, Handle};
+use phosphor::{component, resource};
 use log_once::warn_once;
 use rand::Rng;
 use serde::{Serialize, Deserialize};
+use render_graph::{RenderGraph, Pass, PassContext, Target, Depth, Size, View};
 
 const SHADOW_RES: u32 = 4096;
+const MAX_LIGHTS: usize = 100;
 
 #[derive(Serialize, Deserialize)]
 #[component]
@@ -109,7 +117,7 @@ impl Model {
   }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[component]
 pub struct Material {
   pub color: Vec3,
@@ -127,6 +135,17 @@ impl Material {
   };
 }
 
+impl From<&MeshMaterial> for Material {
+  fn from(m: &MeshMaterial) -> Self {
+    Self {
+      color: m.color,
+      tex: m.tex.clone(),
+      spec: m.spec,
+      metallic: 0.0,
+    }
+  }
+}
+
 #[derive(Serialize, Deserialize)]
 #[component]
 pub struct Light {
@@ -148,39 +167,65 @@ impl Light {
   }
 }
 
+#[derive(Serialize, Deserialize)]
+#[resource]
 pub struct SkySettings {
   pub dir: Vec2,
 }
 
+// the std140 blocks in shaders/camera.glsl, shaders/lights.glsl and ssao.frag
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CameraBlock {
+  pub view: Mat4,
+  pub projection: Mat4,
+  pub cam_pos: [f32; 4],
+}
+
+unsafe impl UniformBlock for CameraBlock {}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct LightData {
+  pos: [f32; 3],
+  strength: f32,
+  color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct LightBlock {
+  num_lights: i32,
+  _pad: [i32; 3],
+  lights: [LightData; MAX_LIGHTS],
+}
+
+unsafe impl UniformBlock for LightBlock {}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SsaoBlock {
+  samples: [[f32; 4]; 64],
+}
+
+unsafe impl UniformBlock for SsaoBlock {}
+
 struct SceneRenderer {
-  gbuffer: Framebuffer,
-  galbedo: Texture,
-  gposition: Texture,
-  gnormal: Texture,
-  gmaterial: Texture,
-  quad: Mesh,
-  light_shader: Shader,
-  ssao_samples: Vec<Vec3>,
+  camera_block: UniformBuffer<CameraBlock>,
+  light_block: UniformBuffer<LightBlock>,
+  // filled once at init, only kept alive here
+  _ssao_block: UniformBuffer<SsaoBlock>,
   ssao_noise: Texture,
-  ssao_fb: Framebuffer,
-  ssao_tex: Texture,
-  ssao_shader: Shader,
+  ssao_shader: Handle<Shader>,
+  light_shader: Handle<Shader>,
   sky_mesh: Mesh,
-  sky_shader: Shader,
-  shadow_fb: Framebuffer,
-  shadow_tex: Texture,
-  shadow_shader: Shader,
-  default_shader: Shader,
-}
-
-pub struct ScenePerf {
-  pub shadow_pass: Query,
-  pub geometry_pass: Query,
-  pub ssao_pass: Query,
-  pub lighting_pass: Query,
+  sky_shader: Handle<Shader>,
+  shadow_shader: Handle<Shader>,
+  default_shader: Handle<Shader>,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[resource]
 pub enum Tonemap {
   Aces,
   Filmic,
@@ -201,54 +246,52 @@ impl Tonemap {
   }
 }
 
-fn gbuf() -> Texture {
-  Texture::new(ptr::null(), 0, 0, gl::RGBA16F, gl::RGBA, gl::FLOAT)
+// None if the variant doesnt compile, callers fall back to the shader without the defines
+fn variant(shader: &Shader, defines: &[(&str, &str)]) -> Option<Rc<Shader>> {
+  shader
+    .variant(defines)
+    .map_err(|e| warn_once!("{}", e))
+    .ok()
 }
 
 fn lerp(a: f32, b: f32, t: f32) -> f32 {
   a + t * (b - a)
 }
 
+// direction, view and projection of the sun
+fn sun_matrices(sky: &SkySettings) -> (Vec3, Mat4, Mat4) {
+  let sun_dir = dir(sky.dir.x, sky.dir.y);
+  (
+    sun_dir,
+    Mat4::look_at_rh(sun_dir, Vec3::ZERO, Vec3::Y),
+    // todo calculate this from cam frustum
+    Mat4::orthographic_rh(-15.0, 15.0, -15.0, 15.0, 0.1, 15.0),
+  )
+}
+
 pub fn scenerenderer_plugin(world: &mut World) -> Result {
   world.add_resource(SkySettings {
     dir: Vec2::new(30.0, 320.0),
   });
-  let gbuffer = Framebuffer::new();
-  let galbedo = gbuf();
-  gbuffer.bind_tex(&galbedo, 0);
-  let gposition = gbuf();
-  gbuffer.bind_tex(&gposition, 1);
-  let gnormal = gbuf();
-  gbuffer.bind_tex(&gnormal, 2);
-  let gmaterial = gbuf();
-  gbuffer.bind_tex(&gmaterial, 3);
-  unsafe {
-    gl::DrawBuffers(
-      4,
-      [
-        gl::COLOR_ATTACHMENT0,
-        gl::COLOR_ATTACHMENT1,
-        gl::COLOR_ATTACHMENT2,
-        gl::COLOR_ATTACHMENT3,
-      ]
-      .as_ptr(),
-    );
-  }
 
   let mut rng = rand::thread_rng();
-  let mut ssao_samples = vec![];
-  for i in 0..64 {
-    ssao_samples.push(
-      Vec3::new(
-        rng.gen_range(-1.0..1.0),
-        rng.gen_range(-1.0..1.0),
-        rng.gen_range(0.0..1.0),
-      )
-      .normalize()
-        * rng.gen_range(0.0..1.0)
-        * lerp(0.0, 1.0, (i as f32 / 64.0).powi(2)),
-    );
+  let mut ssao_samples = SsaoBlock {
+    samples: [[0.0; 4]; 64],
+  };
+  for (i, sample) in ssao_samples.samples.iter_mut().enumerate() {
+    *sample = (Vec3::new(
+      rng.gen_range(-1.0..1.0),
+      rng.gen_range(-1.0..1.0),
+      rng.gen_range(0.0..1.0),
+    )
+    .normalize()
+      * rng.gen_range(0.0..1.0)
+      * lerp(0.0, 1.0, (i as f32 / 64.0).powi(2)))
+    .extend(0.0)
+    .to_array();
   }
+  let ssao_block = UniformBuffer::new("Ssao");
+  ssao_block.update(&ssao_samples);
   let mut ssao_noise = vec![];
   for _ in 0..16 {
     ssao_noise.push(Vec3::new(
@@ -265,51 +308,14 @@ pub fn scenerenderer_plugin(world: &mut World) -> Result {
     gl::RGB,
     gl::FLOAT,
   );
-  let ssao_fb = Framebuffer::new_no_depth();
-  let ssao_tex = Texture::new(ptr::null(), 0, 0, gl::RED, gl::RED, gl::FLOAT);
-  ssao_fb.bind_tex(&ssao_tex, 0);
-
-  let shadow_fb = Framebuffer::new_no_depth();
-  let shadow_tex = Texture::new(
-    ptr::null(),
-    SHADOW_RES,
-    SHADOW_RES,
-    gl::DEPTH_COMPONENT,
-    gl::DEPTH_COMPONENT,
-    gl::FLOAT,
-  );
-  shadow_fb.bind_depth(&shadow_tex);
+  let assets = world.get_resource::<Assets>().unwrap();
   world.add_resource(SceneRenderer {
-    gbuffer,
-    galbedo,
-    gposition,
-    gnormal,
-    gmaterial,
-    quad: Mesh::new(
-      &[
-        Vertex {
-          pos: [1.0, 1.0, 0.0],
-          uv: [1.0, 1.0],
-          normal: [0.0, 0.0, 0.0],
-        },
-        Vertex {
-          pos: [1.0, -1.0, 0.0],
-          uv: [1.0, 0.0],
-          normal: [0.0, 0.0, 0.0],
-        },
-        Vertex {
-          pos: [-1.0, -1.0, 0.0],
-          uv: [0.0, 0.0],
-          normal: [0.0, 0.0, 0.0],
-        },
-        Vertex {
-          pos: [-1.0, 1.0, 0.0],
-          uv: [0.0, 1.0],
-          normal: [0.0, 0.0, 0.0],
-        },
-      ],
-      &[0, 1, 3, 1, 2, 3],
-    ),
+    camera_block: UniformBuffer::new("Camera"),
+    light_block: UniformBuffer::new("Lights"),
+    _ssao_block: ssao_block,
+    ssao_noise,
+    ssao_shader: assets.load("shaders/ssao.shader")?,
+    light_shader: assets.load("shaders/light.shader")?,
     sky_mesh: Mesh::new(
       &[
         Vertex {
@@ -335,212 +341,236 @@ pub fn scenerenderer_plugin(world: &mut World) -> Result {
       ],
       &[0, 1, 2, 1, 3, 2],
     ),
-    light_shader: Shader::new("light.vert", "light.frag")?,
-    ssao_samples,
-    ssao_noise,
-    ssao_fb,
-    ssao_tex,
-    ssao_shader: Shader::new("light.vert", "ssao.frag")?,
-    sky_shader: Shader::new("sky.vert", "sky.frag")?,
-    shadow_fb,
-    shadow_tex,
-    shadow_shader: Shader::new("shadow.vert", "shadow.frag")?,
-    default_shader: Shader::new("base.vert", "default.frag")?,
-  });
-  world.add_resource(ScenePerf {
-    shadow_pass: Query::new(),
-    geometry_pass: Query::new(),
-    ssao_pass: Query::new(),
-    lighting_pass: Query::new(),
+    sky_shader: assets.load("shaders/sky.shader")?,
+    shadow_shader: assets.load("shaders/shadow.shader")?,
+    default_shader: assets.load("shaders/default.shader")?,
   });
+
+  let mut graph = RenderGraph::new(assets)?;
+  graph.add_pass(
+    Pass::new("shadow", shadow_pass)
+      .depth(Depth::Target(Target::depth("shadow_map")))
+      .size(Size::Fixed(SHADOW_RES, SHADOW_RES)),
+  )?;
+  graph.add_pass(
+    Pass::new("geometry", geometry_pass)
+      .output(Target::color("galbedo"))
+      .output(Target::color("gposition"))
+      .output(Target::color("gnormal"))
+      .output(Target::color("gmaterial"))
+      .depth(Depth::Buffer),
+  )?;
+  graph.add_pass(
+    Pass::new("ssao", ssao_pass)
+      .input("galbedo")
+      .input("gposition")
+      .input("gnormal")
+      .output(Target::new("ssao_tex", gl::RED, gl::RED, gl::FLOAT)),
+  )?;
+  graph.add_pass(
+    Pass::new("lighting", lighting_pass)
+      .input("galbedo")
+      .input("gposition")
+      .input("gnormal")
+      .input("gmaterial")
+      .input("ssao_tex")
+      .input("shadow_map")
+      .output(Target::color("color")),
+  )?;
+  world.add_resource(graph);
   world.add_resource(Tonemap::Reinhard);
   world.add_system(stage::DRAW, scenerenderer_draw);
   Ok(())
 }
 
+// draws from the sun, the camera block is put back for the passes after
+fn shadow_pass(world: &World, ctx: &PassContext) -> Result {
+  let r = world.get_resource::<SceneRenderer>().unwrap();
+  let (_, sun_view, sun_projection) = sun_matrices(world.get_resource::<SkySettings>().unwrap());
+  r.camera_block.update(&CameraBlock {
+    view: sun_view,
+    projection: sun_projection,
+    cam_pos: sun_view.inverse().w_axis.to_array(),
+  });
+  let shadow_shader = r.shadow_shader.loaded()?;
+  shadow_shader.bind();
+  for (e, model) in world.query::<Model>() {
+    if model.cast_shadows {
+      if let (Some(model_t), Some(mesh)) = (e.get_one::<Transform>(), model.mesh.get()) {
+        shadow_shader.set_mat4("model", &model_t.as_mat4());
+        mesh.draw();
+      }
+    }
+  }
+  r.camera_block.update(&CameraBlock {
+    view: ctx.view.view,
+    projection: ctx.view.projection,
+    cam_pos: ctx.view.cam_pos.extend(1.0).to_array(),
+  });
+  Ok(())
+}
+
+fn geometry_pass(world: &World, _: &PassContext) -> Result {
+  let r = world.get_resource::<SceneRenderer>().unwrap();
+  let (sun_dir, _, _) = sun_matrices(world.get_resource::<SkySettings>().unwrap());
+  let sky_shader = r.sky_shader.loaded()?;
+  sky_shader.bind();
+  sky_shader.set_vec3("sun_dir", &sun_dir);
+  unsafe {
+    gl::DepthMask(gl::FALSE);
+    r.sky_mesh.draw();
+    gl::DepthMask(gl::TRUE);
+  }
+
+  let plain = &*r.default_shader.loaded()?;
+  let textured = variant(plain, &[("USE_TEX", "1")]);
+  let textured = textured.as_deref().unwrap_or(plain);
+  for (e, model) in world.query::<Model>() {
+    // still loading
+    let Some(mesh) = model.mesh.get() else {
+      continue;
+    };
+    match e.get_one::<Transform>() {
+      Some(model_t) => {
+        unsafe {
+          gl::PolygonMode(
+            gl::FRONT_AND_BACK,
+            if model.wireframe { gl::LINE } else { gl::FILL },
+          );
+        }
+        // the nth material draws the nth submesh, then the mtl material, then the first material
+        let mats = e.get::<Material>();
+        for (i, submesh) in mesh.submeshes.iter().enumerate() {
+          let mtl = submesh.material.as_ref().map(Material::from);
+          let mat = mats
+            .get(i)
+            .map(|m| &**m)
+            .or(mtl.as_ref())
+            .or(mats.first().map(|m| &**m))
+            .unwrap_or(&Material::DEFAULT);
+          let shader = match mat.tex.as_ref().and_then(|t| t.get()) {
+            Some(tex) => {
+              tex.bind(0);
+              textured
+            }
+            None => plain,
+          };
+          shader.bind();
+          shader.set_mat4("model", &model_t.as_mat4());
+          shader.set_vec3("color", &mat.color);
+          shader.set_f32("spec", &mat.spec);
+          shader.set_f32("metallic", &mat.metallic);
+          mesh.draw_submesh(i);
+        }
+      }
+      None => warn_once!(
+        "Mesh on entity '{}'({}) won't be rendered (Missing Transform).",
+        e.get_one::<Name>().map_or("?", |n| &n.0),
+        e.id
+      ),
+    }
+  }
+  unsafe {
+    gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+  }
+  Ok(())
+}
+
+fn ssao_pass(world: &World, ctx: &PassContext) -> Result {
+  let r = world.get_resource::<SceneRenderer>().unwrap();
+  let ssao_shader = r.ssao_shader.loaded()?;
+  ssao_shader.bind();
+  ctx.set_inputs(&ssao_shader);
+  r.ssao_noise.bind(3);
+  ssao_shader.set_i32("noise", &3);
+  ctx.draw_quad();
+  Ok(())
+}
+
+fn lighting_pass(world: &World, ctx: &PassContext) -> Result {
+  let r = world.get_resource::<SceneRenderer>().unwrap();
+  let (sun_dir, sun_view, sun_projection) =
+    sun_matrices(world.get_resource::<SkySettings>().unwrap());
+  let tonemap = *world
+    .get_resource::<Tonemap>()
+    .unwrap_or(&mut Tonemap::Aces) as i32;
+  let light_shader = r.light_shader.loaded()?;
+  let shader = variant(&light_shader, &[("TONEMAP", &tonemap.to_string())]);
+  let shader = shader.as_deref().unwrap_or(&light_shader);
+  shader.bind();
+  ctx.set_inputs(shader);
+  shader.set_vec3("sun_dir", &sun_dir);
+  shader.set_mat4("sun_view", &sun_view);
+  shader.set_mat4("sun_projection", &sun_projection);
+  ctx.draw_quad();
+  Ok(())
+}
+
 pub struct SceneDrawOptions {
-  pub fb: Framebuffer,
+  pub fb: Rc<Framebuffer>,
   pub size: [f32; 2],
 }
 
 fn scenerenderer_draw(world: &mut World) -> Result {
   let renderer = world.get_resource::<Renderer>().unwrap();
   let (w, h) = renderer.window.get_framebuffer_size();
-  match world.query::<Camera>().get(0) {
+  let mut result = Ok(());
+  match world.query::<Camera>().first() {
     Some((e, cam)) => match e.get_one::<Transform>() {
       Some(cam_t) => {
         let r = world.get_resource::<SceneRenderer>().unwrap();
-        let perf = world.get_resource::<ScenePerf>().unwrap();
-        let sky = world.get_resource::<SkySettings>().unwrap();
-        let sun_dir = dir(sky.dir.x, sky.dir.y);
-        let sun_view = Mat4::look_at_rh(sun_dir, Vec3::ZERO, Vec3::Y);
-        // todo calculate this from cam frustum
-        let sun_projection = Mat4::orthographic_rh(-15.0, 15.0, -15.0, 15.0, 0.1, 15.0);
-
-        // shadow pass
-        perf.shadow_pass.time(|| {
-          r.shadow_fb.bind();
-          renderer.resize(SHADOW_RES, SHADOW_RES);
-          renderer.clear(0.0, 0.0, 0.0, 1.0);
-          r.shadow_shader.bind();
-          r.shadow_shader.set_mat4("view", &sun_view);
-          r.shadow_shader.set_mat4("projection", &sun_projection);
-          for (e, model) in world.query::<Model>() {
-            if model.cast_shadows {
-              if let Some(model_t) = e.get_one::<Transform>() {
-                r.shadow_shader.set_mat4("model", &model_t.as_mat4());
-                model.mesh.draw();
-              }
-            }
-          }
-        });
-
+        let default = Framebuffer::DEFAULT;
         let (fb, w, h) = match world.get_resource::<SceneDrawOptions>() {
-          Some(o) => (o.fb, o.size[0], o.size[1]),
-          None => (Framebuffer::DEFAULT, w as _, h as _),
+          Some(o) => (&*o.fb, o.size[0], o.size[1]),
+          None => (&default, w as _, h as _),
         };
         let (view, projection) = cam.matrices(cam_t, w / h);
-        // geometry pass
-        perf.geometry_pass.time(|| {
-          r.gbuffer.bind();
-          renderer.resize(w as _, h as _);
-          r.gbuffer.resize(w as _, h as _);
-          r.galbedo.resize(w as _, h as _);
-          r.gposition.resize(w as _, h as _);
-          r.gnormal.resize(w as _, h as _);
-          r.gmaterial.resize(w as _, h as _);
-          r.ssao_fb.resize(w as _, h as _);
-          r.ssao_tex.resize(w as _, h as _);
-          renderer.clear(0.0, 0.0, 0.0, 1.0);
-
-          r.sky_shader.bind();
-          r.sky_shader.set_mat4("view", &view);
-          r.sky_shader.set_mat4("projection", &projection);
-          r.sky_shader.set_vec3("sun_dir", &sun_dir);
-          unsafe {
-            gl::DepthMask(gl::FALSE);
-            r.sky_mesh.draw();
-            gl::DepthMask(gl::TRUE);
-          }
-
-          r.default_shader.bind();
-          r.default_shader.set_mat4("view", &view);
-          r.default_shader.set_mat4("projection", &projection);
-          for (e, model) in world.query::<Model>() {
-            match e.get_one::<Transform>() {
-              Some(model_t) => {
-                let mat = match e.get_one::<Material>() {
-                  Some(m) => m,
-                  None => &Material::DEFAULT,
-                };
-                match &mat.tex {
-                  Some(tex) => {
-                    tex.bind(0);
-                    r.default_shader.set_i32("use_tex", &1);
-                  }
-                  None => r.default_shader.set_i32("use_tex", &0),
-                };
-                r.default_shader.set_vec3("color", &mat.color);
-                r.default_shader.set_f32("spec", &mat.spec);
-                r.default_shader.set_f32("metallic", &mat.metallic);
-                r.default_shader.set_mat4("model", &model_t.as_mat4());
-                unsafe {
-                  gl::PolygonMode(
-                    gl::FRONT_AND_BACK,
-                    if model.wireframe { gl::LINE } else { gl::FILL },
-                  );
-                }
-                model.mesh.draw();
-              }
-              None => warn_once!(
-                "Mesh on entity '{}'({}) won't be rendered (Missing Transform).",
-                e.get_one::<Name>().map_or("?", |n| &n.0),
-                e.id
-              ),
-            }
-          }
+        r.camera_block.bind();
+        r.camera_block.update(&CameraBlock {
+          view,
+          projection,
+          cam_pos: cam_t.position.extend(1.0).to_array(),
         });
-
-        // ssao pass
-        perf.ssao_pass.time(|| {
-          r.ssao_fb.bind();
-          renderer.clear(0.0, 0.0, 0.0, 1.0);
-          r.ssao_shader.bind();
-          r.galbedo.bind(0);
-          r.ssao_shader.set_i32("galbedo", &0);
-          r.gposition.bind(1);
-          r.ssao_shader.set_i32("gposition", &1);
-          r.gnormal.bind(2);
-          r.ssao_shader.set_i32("gnormal", &2);
-          r.ssao_noise.bind(3);
-          r.ssao_shader.set_i32("noise", &3);
-          for (i, s) in r.ssao_samples.iter().enumerate() {
-            r.ssao_shader.set_vec3(&format!("samples[{}]", i), s);
-          }
-          r.ssao_shader.set_mat4("view", &view);
-          r.ssao_shader.set_mat4("projection", &projection);
-          r.quad.draw();
-        });
-
-        // lighting pass
-        perf.lighting_pass.time(|| {
-          fb.bind();
-          renderer.clear(0.0, 0.0, 0.0, 1.0);
-          r.light_shader.bind();
-          r.galbedo.bind(0);
-          r.light_shader.set_i32("galbedo", &0);
-          r.gposition.bind(1);
-          r.light_shader.set_i32("gposition", &1);
-          r.gnormal.bind(2);
-          r.light_shader.set_i32("gnormal", &2);
-          r.gmaterial.bind(3);
-          r.light_shader.set_i32("gmaterial", &3);
-          r.ssao_tex.bind(4);
-          r.light_shader.set_i32("ssao_tex", &4);
-          r.shadow_tex.bind(5);
-          r.light_shader.set_mat4("view", &view);
-          r.light_shader.set_mat4("projection", &projection);
-          r.light_shader.set_i32("shadow_map", &5);
-          r.light_shader.set_vec3("cam_pos", &cam_t.position);
-          r.light_shader.set_vec3("sun_dir", &sun_dir);
-          r.light_shader.set_mat4("sun_view", &sun_view);
-          r.light_shader.set_mat4("sun_projection", &sun_projection);
-          r.light_shader.set_i32(
-            "tonemap",
-            &(*world
-              .get_resource::<Tonemap>()
-              .unwrap_or(&mut Tonemap::Aces) as i32),
-          );
-          let lights = world.query::<Light>();
-          for (i, (e, light)) in lights.iter().enumerate() {
-            match e.get_one::<Transform>() {
-              Some(light_t) => {
-                r.light_shader
-                  .set_vec3(&format!("lights[{}].pos", i), &light_t.position);
-                r.light_shader
-                  .set_vec3(&format!("lights[{}].color", i), &light.color);
-                r.light_shader
-                  .set_f32(&format!("lights[{}].strength", i), &light.strength);
-              }
-              None => warn_once!(
-                "Light on entity '{}'({}) will not be rendered (Missing transform).",
-                e.get_one::<Name>().map_or("?", |n| &n.0),
-                e.id
-              ),
+        let mut lights = LightBlock {
+          num_lights: 0,
+          _pad: [0; 3],
+          lights: [LightData::default(); MAX_LIGHTS],
+        };
+        for (e, light) in world.query::<Light>() {
+          match e.get_one::<Transform>() {
+            Some(_) if lights.num_lights as usize == MAX_LIGHTS => {
+              warn_once!("Only {} lights will be rendered.", MAX_LIGHTS)
+            }
+            Some(light_t) => {
+              lights.lights[lights.num_lights as usize] = LightData {
+                pos: light_t.position.to_array(),
+                strength: light.strength,
+                color: light.color.extend(0.0).to_array(),
+              };
+              lights.num_lights += 1;
             }
+            None => warn_once!(
+              "Light on entity '{}'({}) will not be rendered (Missing transform).",
+              e.get_one::<Name>().map_or("?", |n| &n.0),
+              e.id
+            ),
           }
-          r.light_shader.set_i32("num_lights", &(lights.len() as _));
-          r.quad.draw();
-        });
+        }
+        r.light_block.update(&lights);
+
+        let view = View {
+          view,
+          projection,
+          cam_pos: cam_t.position,
+        };
+        let graph = world.get_resource::<RenderGraph>().unwrap();
+        result = graph.run(world, fb, w as _, h as _, &view);
       }
       None => warn_once!("Scene will not be rendered (Missing camera transform)."),
     },
     None => warn_once!("Scene will not be rendered (Missing camera)."),
   };
-  unsafe {
-    gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
-  }
   Framebuffer::DEFAULT.bind();
   renderer.resize(w as _, h as _);
-  Ok(())
+  result
 }
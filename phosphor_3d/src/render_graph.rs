@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::ptr;
+use phosphor::Result;
+use phosphor::ecs::World;
+use phosphor::assets::{Assets, Handle};
+use phosphor::gfx::{Renderer, Shader, Texture, Mesh, Framebuffer, Vertex, Query, gl};
+use phosphor::math::{Mat4, Vec3};
+
+// how big a passes targets are
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Size {
+  // the size of the output, eg the window or the editor viewport
+  Output,
+  // a fraction of the output size, eg 0.5 for half res
+  Scaled(f32),
+  Fixed(u32, u32),
+}
+
+impl Size {
+  fn resolve(self, w: u32, h: u32) -> (u32, u32) {
+    match self {
+      Self::Output => (w, h),
+      Self::Scaled(s) => (
+        ((w as f32 * s) as u32).max(1),
+        ((h as f32 * s) as u32).max(1),
+      ),
+      Self::Fixed(w, h) => (w, h),
+    }
+  }
+}
+
+// a texture written by a pass, passes after it can read it by name
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Target {
+  pub name: &'static str,
+  pub iformat: u32,
+  pub format: u32,
+  pub typ: u32,
+}
+
+impl Target {
+  pub const fn new(name: &'static str, iformat: u32, format: u32, typ: u32) -> Self {
+    Self {
+      name,
+      iformat,
+      format,
+      typ,
+    }
+  }
+
+  pub const fn color(name: &'static str) -> Self {
+    Self::new(name, gl::RGBA16F, gl::RGBA, gl::FLOAT)
+  }
+
+  pub const fn depth(name: &'static str) -> Self {
+    Self::new(name, gl::DEPTH_COMPONENT, gl::DEPTH_COMPONENT, gl::FLOAT)
+  }
+}
+
+pub enum Depth {
+  None,
+  // a depth and stencil buffer only this pass uses
+  Buffer,
+  // a depth texture later passes can read
+  Target(Target),
+}
+
+// what a pass is drawing, the camera matrices are also in the Camera uniform block
+pub struct View {
+  pub view: Mat4,
+  pub projection: Mat4,
+  pub cam_pos: Vec3,
+}
+
+pub struct PassContext<'a> {
+  pub view: &'a View,
+  pub width: u32,
+  pub height: u32,
+  inputs: &'a [&'static str],
+  quad: &'a Mesh,
+}
+
+impl PassContext<'_> {
+  // inputs are bound to texture units in the order they were declared, with samplers named after the target
+  pub fn set_inputs(&self, shader: &Shader) {
+    for (i, name) in self.inputs.iter().enumerate() {
+      shader.set_i32(name, &(i as _));
+    }
+  }
+
+  // covers the whole target, for post effects
+  pub fn draw_quad(&self) {
+    self.quad.draw();
+  }
+}
+
+pub type PassFn = dyn Fn(&World, &PassContext) -> Result;
+
+pub struct Pass {
+  pub name: &'static str,
+  pub query: Query,
+  inputs: Vec<&'static str>,
+  outputs: Vec<Target>,
+  depth: Depth,
+  size: Size,
+  run: Box<PassFn>,
+  fb: Option<Framebuffer>,
+  // targets first written by this pass, it clears and resizes them
+  owned: Vec<&'static str>,
+  current_size: (u32, u32),
+}
+
+impl Pass {
+  pub fn new<F: Fn(&World, &PassContext) -> Result + 'static>(name: &'static str, run: F) -> Self {
+    Self {
+      name,
+      query: Query::new(),
+      inputs: vec![],
+      outputs: vec![],
+      depth: Depth::None,
+      size: Size::Output,
+      run: Box::new(run),
+      fb: None,
+      owned: vec![],
+      current_size: (0, 0),
+    }
+  }
+
+  pub fn input(mut self, name: &'static str) -> Self {
+    self.inputs.push(name);
+    self
+  }
+
+  // color attachments, in order
+  pub fn output(mut self, target: Target) -> Self {
+    self.outputs.push(target);
+    self
+  }
+
+  pub fn depth(mut self, depth: Depth) -> Self {
+    self.depth = depth;
+    self
+  }
+
+  pub fn size(mut self, size: Size) -> Self {
+    self.size = size;
+    self
+  }
+
+  fn writes(&self) -> impl Iterator<Item = &Target> {
+    let depth = match &self.depth {
+      Depth::Target(t) => Some(t),
+      _ => None,
+    };
+    self.outputs.iter().chain(depth)
+  }
+}
+
+// passes run in order, each reading targets written by the passes before it
+// plugins can insert their own passes, eg outlines after "geometry" or post effects presenting a new target
+pub struct RenderGraph {
+  passes: Vec<Pass>,
+  targets: HashMap<&'static str, Texture>,
+  present: &'static str,
+  dirty: bool,
+  quad: Mesh,
+  blit_shader: Handle<Shader>,
+}
+
+impl RenderGraph {
+  pub fn new(assets: &mut Assets) -> Result<Self> {
+    Ok(Self {
+      passes: vec![],
+      targets: HashMap::new(),
+      present: "color",
+      dirty: true,
+      quad: Mesh::new(
+        &[
+          Vertex {
+            pos: [1.0, 1.0, 0.0],
+            uv: [1.0, 1.0],
+            normal: [0.0, 0.0, 0.0],
+          },
+          Vertex {
+            pos: [1.0, -1.0, 0.0],
+            uv: [1.0, 0.0],
+            normal: [0.0, 0.0, 0.0],
+          },
+          Vertex {
+            pos: [-1.0, -1.0, 0.0],
+            uv: [0.0, 0.0],
+            normal: [0.0, 0.0, 0.0],
+          },
+          Vertex {
+            pos: [-1.0, 1.0, 0.0],
+            uv: [0.0, 1.0],
+            normal: [0.0, 0.0, 0.0],
+          },
+        ],
+        &[0, 1, 3, 1, 2, 3],
+      ),
+      blit_shader: assets.load("shaders/blit.shader")?,
+    })
+  }
+
+  pub fn add_pass(&mut self, pass: Pass) -> Result {
+    self.insert(self.passes.len(), pass)
+  }
+
+  pub fn add_pass_before(&mut self, before: &str, pass: Pass) -> Result {
+    let i = self.position(before)?;
+    self.insert(i, pass)
+  }
+
+  pub fn add_pass_after(&mut self, after: &str, pass: Pass) -> Result {
+    let i = self.position(after)?;
+    self.insert(i + 1, pass)
+  }
+
+  pub fn passes(&mut self) -> impl Iterator<Item = &mut Pass> {
+    self.passes.iter_mut()
+  }
+
+  pub fn present(&self) -> &'static str {
+    self.present
+  }
+
+  // the target drawn to the output once every pass has run
+  pub fn set_present(&mut self, name: &'static str) -> Result {
+    if !self
+      .passes
+      .iter()
+      .any(|p| p.outputs.iter().any(|t| t.name == name))
+    {
+      return Err(format!("No render pass writes '{}'.", name).into());
+    }
+    self.present = name;
+    Ok(())
+  }
+
+  fn position(&self, name: &str) -> Result<usize> {
+    self
+      .passes
+      .iter()
+      .position(|p| p.name == name)
+      .ok_or_else(|| format!("Couldnt find render pass '{}'.", name).into())
+  }
+
+  fn insert(&mut self, i: usize, pass: Pass) -> Result {
+    self.passes.insert(i, pass);
+    if let Err(e) = validate(&self.passes) {
+      self.passes.remove(i);
+      return Err(e);
+    }
+    self.dirty = true;
+    Ok(())
+  }
+
+  // creates every framebuffer and target, they get sized on the next run
+  fn compile(&mut self) {
+    self.targets.clear();
+    for p in self.passes.iter_mut() {
+      p.current_size = (0, 0);
+      let fb = match p.depth {
+        Depth::Buffer => Framebuffer::new(),
+        _ => Framebuffer::new_no_depth(),
+      };
+      let mut owned = vec![];
+      for t in p.writes() {
+        if !self.targets.contains_key(t.name) {
+          let tex = Texture::new(ptr::null(), 0, 0, t.iformat, t.format, t.typ);
+          self.targets.insert(t.name, tex);
+          owned.push(t.name);
+        }
+      }
+      p.owned = owned;
+      for (i, t) in p.outputs.iter().enumerate() {
+        fb.bind_tex(&self.targets[t.name], i as _);
+      }
+      if let Depth::Target(t) = &p.depth {
+        fb.bind_depth(&self.targets[t.name]);
+      }
+      let attachments: Vec<_> = (0..p.outputs.len() as u32)
+        .map(|i| gl::COLOR_ATTACHMENT0 + i)
+        .collect();
+      unsafe {
+        fb.bind();
+        if attachments.is_empty() {
+          gl::DrawBuffer(gl::NONE);
+          gl::ReadBuffer(gl::NONE);
+        } else {
+          gl::DrawBuffers(attachments.len() as _, attachments.as_ptr());
+        }
+      }
+      p.fb = Some(fb);
+    }
+    self.dirty = false;
+  }
+
+  pub fn run(
+    &mut self,
+    world: &World,
+    output: &Framebuffer,
+    width: u32,
+    height: u32,
+    view: &View,
+  ) -> Result {
+    if self.dirty {
+      self.compile();
+    }
+    let renderer = world.get_resource::<Renderer>().unwrap();
+    for p in self.passes.iter_mut() {
+      let (w, h) = p.size.resolve(width, height);
+      let fb = p.fb.as_ref().unwrap();
+      // only reallocate when the size changes
+      if p.current_size != (w, h) {
+        for name in p.owned.iter() {
+          self.targets.get_mut(name).unwrap().resize(w, h);
+        }
+        if let Depth::Buffer = p.depth {
+          fb.resize(w, h);
+        }
+        p.current_size = (w, h);
+      }
+      fb.bind();
+      renderer.resize(w, h);
+      if !p.owned.is_empty() {
+        renderer.clear(0.0, 0.0, 0.0, 1.0);
+      } else if let Depth::Buffer = p.depth {
+        unsafe { gl::Clear(gl::DEPTH_BUFFER_BIT) };
+      }
+      for (i, name) in p.inputs.iter().enumerate() {
+        self.targets[name].bind(i as _);
+      }
+      let ctx = PassContext {
+        view,
+        width: w,
+        height: h,
+        inputs: &p.inputs,
+        quad: &self.quad,
+      };
+      let mut result = Ok(());
+      p.query.time(|| result = (p.run)(world, &ctx));
+      result.map_err(|e| format!("Error in render pass '{}': {}", p.name, e))?;
+    }
+
+    let tex = self
+      .targets
+      .get(self.present)
+      .ok_or_else(|| format!("No render pass writes '{}'.", self.present))?;
+    output.bind();
+    renderer.resize(width, height);
+    renderer.clear(0.0, 0.0, 0.0, 1.0);
+    tex.bind(0);
+    let blit_shader = self.blit_shader.loaded()?;
+    blit_shader.bind();
+    blit_shader.set_i32("tex", &0);
+    self.quad.draw();
+    Ok(())
+  }
+}
+
+fn validate(passes: &[Pass]) -> Result {
+  // the first pass to write each target
+  let mut written: HashMap<&str, (&Target, Size, &str)> = HashMap::new();
+  for (i, p) in passes.iter().enumerate() {
+    if passes[..i].iter().any(|q| q.name == p.name) {
+      return Err(format!("Render pass '{}' already exists.", p.name).into());
+    }
+    if p.writes().next().is_none() {
+      return Err(format!("Render pass '{}' doesnt write anything.", p.name).into());
+    }
+    for input in p.inputs.iter() {
+      if !written.contains_key(input) {
+        return Err(
+          format!(
+            "Render pass '{}' reads '{}' before any pass writes it.",
+            p.name, input
+          )
+          .into(),
+        );
+      }
+      if p.writes().any(|t| t.name == *input) {
+        return Err(format!("Render pass '{}' reads and writes '{}'.", p.name, input).into());
+      }
+    }
+    for t in p.writes() {
+      match written.get(t.name) {
+        Some((first, size, by)) if *first != t || *size != p.size => {
+          return Err(
+            format!(
+              "Render pass '{}' writes '{}' with a different format or size than '{}'.",
+              p.name, t.name, by
+            )
+            .into(),
+          )
+        }
+        Some(_) => {}
+        None => {
+          written.insert(t.name, (t, p.size, p.name));
+        }
+      }
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::exit;
+use phosphor::{Result, vfs};
+use phosphor::assets::{self, AssetLoader, CookStamp, COOKED_DIR};
+use phosphor::asset_db::AssetDb;
+
+const USAGE: &str = "usage:
+  phosphor-cook [dir] [--clean]
+  phosphor-cook [dir] --pack=<file>
+
+cooks every asset under dir (default 'assets') into dir/.cooked, which is used in place of the sources.
+cooked assets get a .meta if they dont have one, and .metas left behind by moved assets are moved.
+--clean removes cooked files that are no longer used.
+--pack packs dir into an archive to mount instead, cooked files included.
+
+only meshes, textures and cubemaps are cooked. sounds are left to fmod, which decodes mp3s itself,
+and gltfs are already binary.";
+
+fn main() {
+  let args: Vec<String> = env::args().skip(1).collect();
+  let clean = args.iter().any(|a| a == "--clean");
+  let pack = args.iter().find_map(|a| a.strip_prefix("--pack="));
+  let dirs: Vec<_> = args.iter().filter(|a| !a.starts_with("--")).collect();
+  let dir = match dirs.as_slice() {
+    [] => "assets",
+    [dir] => dir.as_str(),
+    _ => {
+      eprintln!("{}", USAGE);
+      exit(2);
+    }
+  };
+  if let Some(out) = pack {
+    if let Err(e) = vfs::pack(dir, out) {
+      eprintln!("error: {}", e);
+      exit(1);
+    }
+    println!("packed {} into {}", dir, out);
+    return;
+  }
+  match cook(Path::new(dir), clean) {
+    Ok(true) => {}
+    Ok(false) => exit(1),
+    Err(e) => {
+      eprintln!("error: {}", e);
+      exit(1);
+    }
+  }
+}
+
+// Ok(false) when any asset failed to cook
+fn cook(dir: &Path, clean: bool) -> Result<bool> {
+  vfs::mount(dir)?;
+  fs::create_dir_all(dir.join(COOKED_DIR))?;
+  let mut db = AssetDb::new();
+  db.set_writable()?;
+  let (mut cooked, mut cached, mut failed) = (0, 0, 0);
+  let mut used = HashSet::new();
+  for path in vfs::files()? {
+    if path.ends_with(".meta") {
+      continue;
+    }
+    let Some((loader, cook_fn)) = assets::loader_for(&path)
+      .ok()
+      .and_then(|l| Some((l, l.cook?)))
+    else {
+      continue;
+    };
+    let out = assets::cooked_path(db.guid(&path)?);
+    used.insert(out.clone());
+    let (size, mtime) = vfs::stat(&path)?;
+    let stamp = CookStamp {
+      size,
+      mtime: mtime.unwrap_or_default(),
+      hash: source_hash(loader, &path)?,
+    };
+    // eg. a checkout changed the mtime but not the source
+    let old = assets::cook_stamp(&path).map(|(_, s)| s);
+    if old.is_some_and(|s| s.hash == stamp.hash) && vfs::exists(&out) {
+      if old != Some(stamp) {
+        assets::save_cook_stamp(&path, &stamp)?;
+      }
+      cached += 1;
+      continue;
+    }
+    match cook_fn(&path) {
+      Ok(data) => {
+        fs::write(dir.join(&out), data)?;
+        assets::save_cook_stamp(&path, &stamp)?;
+        println!("cooked {}", path);
+        cooked += 1;
+      }
+      Err(e) => {
+        println!("{}: {}", path, e);
+        failed += 1;
+      }
+    }
+  }
+  if clean {
+    for entry in fs::read_dir(dir.join(COOKED_DIR))? {
+      let entry = entry?;
+      let name = format!("{}/{}", COOKED_DIR, entry.file_name().to_string_lossy());
+      if !used.contains(&name) {
+        fs::remove_file(entry.path())?;
+        println!("removed {}", name);
+      }
+    }
+  }
+  println!(
+    "{} cooked, {} up to date, {} failed",
+    cooked, cached, failed
+  );
+  Ok(failed == 0)
+}
+
+// changes with the source and its import settings
+fn source_hash(loader: &AssetLoader, path: &str) -> Result<u64> {
+  let mut h = Fnv::default();
+  h.write(loader.id.name.as_bytes());
+  h.write(&vfs::read(path)?);
+  let meta = format!("{}.meta", path);
+  if vfs::exists(&meta) {
+    let mut settings: serde_json::Map<String, serde_json::Value> =
+      serde_json::from_slice(&vfs::read(&meta)?)?;
+    settings.remove("guid");
+    settings.remove("cooked");
+    h.write(serde_json::to_string(&settings)?.as_bytes());
+  }
+  Ok(h.0)
+}
+
+// fnv-1a, hashes have to stay the same across builds which DefaultHasher doesnt promise
+struct Fnv(u64);
+
+impl Default for Fnv {
+  fn default() -> Self {
+    Self(0xcbf29ce484222325)
+  }
+}
+
+impl Fnv {
+  fn write(&mut self, bytes: &[u8]) {
+    for b in bytes.iter().chain(&[0xff]) {
+      self.0 = (self.0 ^ *b as u64).wrapping_mul(0x100000001b3);
+    }
+  }
+}
@@ -1,7 +1,9 @@
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::{quote, format_ident};
-use syn::{parse_macro_input, Item, ItemStruct, ItemEnum, AttributeArgs, NestedMeta, Meta, Ident};
+use std::collections::HashMap;
+use syn::{parse_macro_input, Item, ItemStruct, ItemEnum, Ident, Expr, Token};
+use syn::parse::{Parse, ParseStream};
 
 fn get_crate() -> Ident {
   match std::env::var("CARGO_PKG_NAME").unwrap().as_str() {
@@ -11,6 +13,7 @@ fn get_crate() -> Ident {
 }
 
 fn enum_struct<F: Fn(TokenStream2, Ident) -> TokenStream2>(
+  attr: &str,
   input: TokenStream,
   f: F,
 ) -> TokenStream {
@@ -19,61 +22,200 @@ fn enum_struct<F: Fn(TokenStream2, Ident) -> TokenStream2>(
     Item::Struct(ItemStruct { ident, .. }) | Item::Enum(ItemEnum { ident, .. }) => {
       f(TokenStream2::from(input), ident).into()
     }
-    _ => quote! {compile_error!("#[component] can only be used on structs or enums.");}.into(),
+    _ => {
+      let msg = format!("#[{}] can only be used on structs or enums.", attr);
+      quote! {compile_error!(#msg);}.into()
+    }
   }
 }
 
-#[proc_macro_attribute]
-pub fn component(_: TokenStream, input: TokenStream) -> TokenStream {
-  enum_struct(input, |input, ident| {
+fn serde_loader(attr: &str, slice: &str, input: TokenStream) -> TokenStream {
+  enum_struct(attr, input, |input, ident| {
     let phosphor = get_crate();
-    let save = format_ident!("{}_SAVE", ident);
-    let load = format_ident!("{}_LOAD", ident);
-    let var = format_ident!("{}_LOADER", ident);
+    let slice = format_ident!("{}", slice);
+    let kind = attr.to_uppercase();
+    let save = format_ident!("{}_{}_SAVE", ident, kind);
+    let load = format_ident!("{}_{}_LOAD", ident, kind);
+    let var = format_ident!("{}_{}_LOADER", ident, kind);
     quote! {
       #[allow(non_snake_case)]
-      fn #save(data: &Box<dyn std::any::Any>) -> Vec<u8>{
-        #phosphor::bincode::serialize(&data.downcast_ref::<#ident>().unwrap()).unwrap()
+      fn #save(data: &Box<dyn std::any::Any>, _: &#phosphor::ecs::World) -> #phosphor::Result<Vec<u8>> {
+        Ok(#phosphor::bincode::serialize(&data.downcast_ref::<#ident>().unwrap())?)
       }
       #[allow(non_snake_case)]
-      fn #load(data: Vec<u8>, _: &mut #phosphor::assets::Assets) -> Box<dyn std::any::Any> {
-        Box::new(#phosphor::bincode::deserialize::<#ident>(&data).unwrap())
+      fn #load(data: &[u8], _: &#phosphor::ecs::World) -> #phosphor::Result<Box<dyn std::any::Any>> {
+        Ok(Box::new(#phosphor::bincode::deserialize::<#ident>(data)?))
       }
       #[allow(non_upper_case_globals)]
-      #[#phosphor::linkme::distributed_slice(#phosphor::scene::COMPONENT_LOADERS)]
+      #[#phosphor::linkme::distributed_slice(#phosphor::scene::#slice)]
       static #var: #phosphor::scene::Loader = #phosphor::scene::Loader {
         id: #phosphor::TypeIdNamed::of::<#ident>(),
         save: #save,
-        load: #load
+        load: #load,
+        restore: None,
+        to_json: #phosphor::scene::to_json::<#ident>,
+        from_json: #phosphor::scene::from_json::<#ident>,
       };
       #input
     }
   })
 }
 
+#[proc_macro_attribute]
+pub fn component(_: TokenStream, input: TokenStream) -> TokenStream {
+  serde_loader("component", "COMPONENT_LOADERS", input)
+}
+
+#[proc_macro_attribute]
+pub fn resource(_: TokenStream, input: TokenStream) -> TokenStream {
+  serde_loader("resource", "RESOURCE_LOADERS", input)
+}
+
+// `#[asset(load_fn)]` or `#[asset(name = value, ...)]`, both can have `ext = ["png", ...]`
+struct AssetArgs {
+  load: Option<Ident>,
+  named: HashMap<String, Expr>,
+}
+
+impl Parse for AssetArgs {
+  fn parse(input: ParseStream) -> syn::Result<Self> {
+    let mut args = AssetArgs {
+      load: None,
+      named: HashMap::new(),
+    };
+    while !input.is_empty() {
+      let name: Ident = input.parse()?;
+      if input.peek(Token![=]) {
+        input.parse::<Token![=]>()?;
+        args.named.insert(name.to_string(), input.parse()?);
+      } else {
+        args.load = Some(name);
+      }
+      if !input.is_empty() {
+        input.parse::<Token![,]>()?;
+      }
+    }
+    Ok(args)
+  }
+}
+
 #[proc_macro_attribute]
 pub fn asset(args: TokenStream, input: TokenStream) -> TokenStream {
-  let args = parse_macro_input!(args as AttributeArgs);
-  enum_struct(input, |input, ident| match args.first() {
-    Some(NestedMeta::Meta(Meta::Path(p))) => {
-      let phosphor = get_crate();
-      let func = p.get_ident().unwrap();
-      let new_func = format_ident!("_{}", func);
-      let var = format_ident!("{}_LOADER", ident);
-      quote! {
-        fn #new_func(world: &mut World, path: &str) -> #phosphor::Result<std::rc::Rc<dyn std::any::Any>> {
-          Ok(std::rc::Rc::new(#func(world, path)?))
+  let args = parse_macro_input!(args as AssetArgs);
+  enum_struct("asset", input, |input, ident| {
+    let phosphor = get_crate();
+    let load = format_ident!("{}_ASSET_LOAD", ident);
+    let decode = format_ident!("{}_ASSET_DECODE", ident);
+    let upload = format_ident!("{}_ASSET_UPLOAD", ident);
+    let var = format_ident!("{}_LOADER", ident);
+    let memory = match args.named.get("memory") {
+      Some(m) => {
+        let f = format_ident!("{}_ASSET_MEMORY", ident);
+        quote! {
+          memory: Some({
+            #[allow(non_snake_case)]
+            fn #f(data: &dyn std::any::Any) -> #phosphor::assets::AssetMemory {
+              #m(data.downcast_ref::<#ident>().unwrap())
+            }
+            #f
+          }),
+        }
+      }
+      None => quote! {
+        memory: None,
+      },
+    };
+    // file extensions for `Assets::load_untyped`
+    let exts = match args.named.get("ext") {
+      Some(e) => quote! { &#e },
+      None => quote! { &[] },
+    };
+    // `cook = Type` stores decoded data of that type with bincode, see phosphor_cook
+    let cook = match (args.named.get("cook"), args.named.get("decode")) {
+      (Some(ty), Some(d)) => {
+        let cook = format_ident!("{}_ASSET_COOK", ident);
+        let uncook = format_ident!("{}_ASSET_UNCOOK", ident);
+        quote! {
+          cook: Some({
+            #[allow(non_snake_case)]
+            fn #cook(path: &str) -> #phosphor::Result<Vec<u8>> {
+              let data: #ty = #d(path)?;
+              Ok(#phosphor::bincode::serialize(&data)?)
+            }
+            #cook
+          }),
+          uncook: Some({
+            #[allow(non_snake_case)]
+            fn #uncook(data: &[u8]) -> #phosphor::Result<#phosphor::assets::Decoded> {
+              Ok(Box::new(#phosphor::bincode::deserialize::<#ty>(data)?))
+            }
+            #uncook
+          }),
         }
-
-        #[allow(non_upper_case_globals)]
-        #[#phosphor::linkme::distributed_slice(#phosphor::assets::ASSET_LOADERS)]
-        static #var: #phosphor::assets::AssetLoader = #phosphor::assets::AssetLoader {
-          id: #phosphor::TypeIdNamed::of::<#ident>(),
-          loader: #new_func,
-        };
-        #input
       }
+      (Some(_), None) => {
+        return quote! {compile_error!("cook requires decode and upload.");};
+      }
+      _ => quote! {
+        cook: None,
+        uncook: None,
+      },
+    };
+    let (funcs, split) = match (
+      &args.load,
+      args.named.get("decode"),
+      args.named.get("upload"),
+    ) {
+      (Some(func), None, None) => (
+        quote! {
+          #[allow(non_snake_case)]
+          fn #load(world: &mut #phosphor::ecs::World, path: &str) -> #phosphor::Result<std::rc::Rc<dyn std::any::Any>> {
+            Ok(std::rc::Rc::new(#func(world, path)?))
+          }
+        },
+        quote! {
+          decode: None,
+          upload: None,
+        },
+      ),
+      (None, Some(d), Some(u)) => (
+        quote! {
+          #[allow(non_snake_case)]
+          fn #load(world: &mut #phosphor::ecs::World, path: &str) -> #phosphor::Result<std::rc::Rc<dyn std::any::Any>> {
+            Ok(std::rc::Rc::new(#u(world, #d(path)?)?))
+          }
+          #[allow(non_snake_case)]
+          fn #decode(path: &str) -> #phosphor::Result<#phosphor::assets::Decoded> {
+            Ok(Box::new(#d(path)?))
+          }
+          #[allow(non_snake_case)]
+          fn #upload(world: &mut #phosphor::ecs::World, data: #phosphor::assets::Decoded) -> #phosphor::Result<std::rc::Rc<dyn std::any::Any>> {
+            Ok(std::rc::Rc::new(#u(world, *data.downcast().unwrap())?))
+          }
+        },
+        quote! {
+          decode: Some(#decode),
+          upload: Some(#upload),
+        },
+      ),
+      _ => {
+        return quote! {compile_error!("expected #[asset(load_fn)] or #[asset(decode = f, upload = g)].");}
+      }
+    };
+    quote! {
+      #funcs
+
+      #[allow(non_upper_case_globals)]
+      #[#phosphor::linkme::distributed_slice(#phosphor::assets::ASSET_LOADERS)]
+      static #var: #phosphor::assets::AssetLoader = #phosphor::assets::AssetLoader {
+        id: #phosphor::TypeIdNamed::of::<#ident>(),
+        loader: #load,
+        exts: #exts,
+        #split
+        #cook
+        #memory
+      };
+      #input
     }
-    _ => quote! {compile_error!("invalid syntax.")},
   })
 }
@@ -3,19 +3,23 @@
 mod panels;
 
 use std::fs;
-use phosphor::{Engine, Result};
+use phosphor::{Engine, Result, vfs};
 use phosphor::ecs::{World, Entity, stage};
 use phosphor::scene::Scene;
-use phosphor::log::{LevelFilter, error};
-use phosphor::glfw::{WindowEvent, Key, Modifiers};
+use phosphor::gfx::Renderer;
+use phosphor::assets::Assets;
+use phosphor::log::{LevelFilter, error, warn};
+use phosphor::glfw::{WindowEvent, Key, Action, Modifiers};
 use phosphor_imgui::{imgui_plugin, UiRendererOptions};
 use phosphor_imgui::imgui::{Ui, StyleStackToken, Context};
 use phosphor_fmod::{FmodOptions, fmod_plugin};
 use rfd::FileDialog;
-use crate::panels::{Panel, setup_panels};
+use crate::panels::{Panel, SelectedAsset, setup_panels};
 
 pub struct SelectedEntity(Option<Entity>);
 pub struct SceneName(String);
+// snapshot taken when entering play mode
+struct PlayState(Option<Scene>);
 struct Layout(String);
 
 const VER: &str = concat!(
@@ -32,20 +36,12 @@ fn main() -> Result {
       docking: true,
       fonts: &[
         &[
-          ("assets/fonts/roboto.ttf", 16.0, None),
-          (
-            "assets/fonts/fontawesome.ttf",
-            14.0,
-            Some(&[0xe005, 0xf8ff, 0]),
-          ),
+          ("fonts/roboto.ttf", 16.0, None),
+          ("fonts/fontawesome.ttf", 14.0, Some(&[0xe005, 0xf8ff, 0])),
         ],
         &[
-          ("assets/fonts/shingo.otf", 48.0, None),
-          (
-            "assets/fonts/fontawesome.ttf",
-            48.0,
-            Some(&[0xe005, 0xf8ff, 0]),
-          ),
+          ("fonts/shingo.otf", 48.0, None),
+          ("fonts/fontawesome.ttf", 48.0, Some(&[0xe005, 0xf8ff, 0])),
         ],
       ],
     })
@@ -55,6 +51,8 @@ fn main() -> Result {
     .add_resource(SelectedEntity(None))
     .add_resource(SceneName("".to_string()))
     .add_resource(Layout("Default.ini".to_string()))
+    .add_resource(PlayState(None))
+    .add_system(stage::INIT, import_assets)
     .add_system(stage::INIT, imgui_plugin)
     .add_system(stage::INIT, fmod_plugin)
     .add_system(stage::INIT, setup_panels)
@@ -64,6 +62,11 @@ fn main() -> Result {
     .run()
 }
 
+// the editor is what gives assets their .meta, the game only reads them
+fn import_assets(world: &mut World) -> Result {
+  world.get_resource::<Assets>().unwrap().db.set_writable()
+}
+
 fn layout_change(world: &mut World) -> Result {
   if let Some(layout) = world.take_resource::<Layout>() {
     world
@@ -88,6 +91,31 @@ fn draw_ui(world: &mut World) -> Result {
       if ui.menu_item_config("Open").shortcut(shortcut("O")).build() {
         load(mutate(world));
       }
+      if ui.menu_item("Import Asset") {
+        import(world);
+      }
+    });
+    ui.menu("Edit", || {
+      let selected = world.get_resource::<SelectedEntity>().unwrap().0.is_some();
+      if ui
+        .menu_item_config("Copy")
+        .shortcut(shortcut("C"))
+        .enabled(selected)
+        .build()
+      {
+        copy(world);
+      }
+      if ui.menu_item_config("Paste").shortcut(shortcut("V")).build() {
+        paste(world);
+      }
+      if ui
+        .menu_item_config("Duplicate")
+        .shortcut(shortcut("D"))
+        .enabled(selected)
+        .build()
+      {
+        duplicate(world);
+      }
     });
     ui.menu("Windows", || {
       for panel in panels.iter_mut() {
@@ -108,6 +136,14 @@ fn draw_ui(world: &mut World) -> Result {
       //   fs::write("layout", s).unwrap();
       // }
     });
+    let play = world.get_resource::<PlayState>().unwrap();
+    if ui.menu_item(if play.0.is_some() {
+      "\u{f04d} Stop"
+    } else {
+      "\u{f04b} Play"
+    }) {
+      toggle_play(mutate(world));
+    }
     let [w, _] = ui.window_size();
     let [tx, _] = ui.calc_text_size(scene_name.clone());
     ui.same_line_with_pos((w - tx) / 2.0);
@@ -139,6 +175,12 @@ fn shortcut_handler(world: &mut World) -> Result {
   } else {
     Modifiers::Control
   };
+  // dont steal copy/paste from text fields
+  let typing = world
+    .get_resource::<Context>()
+    .unwrap()
+    .io()
+    .want_text_input;
   match world.get_resource::<WindowEvent>().unwrap() {
     WindowEvent::Key(Key::S, _, _, M) => {
       save(world);
@@ -146,11 +188,49 @@ fn shortcut_handler(world: &mut World) -> Result {
     WindowEvent::Key(Key::O, _, _, M) => {
       load(world);
     }
+    WindowEvent::Key(Key::C, _, Action::Press, M) if !typing => copy(world),
+    WindowEvent::Key(Key::V, _, Action::Press, M) if !typing => paste(world),
+    WindowEvent::Key(Key::D, _, Action::Press, M) if !typing => duplicate(world),
     _ => {}
   }
   Ok(())
 }
 
+fn copy(world: &World) {
+  if let Some(e) = world.get_resource::<SelectedEntity>().unwrap().0 {
+    match world.copy(&[e]).to_clipboard() {
+      Ok(s) => world
+        .get_resource::<Renderer>()
+        .unwrap()
+        .window
+        .set_clipboard_string(&s),
+      Err(e) => error!("Couldnt copy entity. {}", e),
+    }
+  }
+}
+
+fn paste(world: &World) {
+  let clipboard = world
+    .get_resource::<Renderer>()
+    .unwrap()
+    .window
+    .get_clipboard_string();
+  match clipboard.map(|s| Scene::from_clipboard(&s)) {
+    Some(Ok(scene)) => {
+      let pasted = world.paste(&scene);
+      world.add_resource(SelectedEntity(pasted.last().copied()));
+    }
+    Some(Err(e)) => warn!("Clipboard doesnt contain entities. {}", e),
+    None => {}
+  }
+}
+
+fn duplicate(world: &World) {
+  if let Some(e) = world.get_resource::<SelectedEntity>().unwrap().0 {
+    world.add_resource(SelectedEntity(Some(world.clone_entity(e))));
+  }
+}
+
 fn save(world: &mut World) {
   if let Some(p) = FileDialog::new().set_file_name("test.scene").save_file() {
     Scene::save(world, p).unwrap();
@@ -161,12 +241,44 @@ fn load(world: &mut World) {
   if let Some(p) = FileDialog::new().pick_file() {
     world.add_resource(SceneName(p.display().to_string()));
     world.add_resource(SelectedEntity(None));
+    world.add_resource(PlayState(None));
     if let Err(e) = Scene::load(world, p.clone()) {
       error!("Couldnt load '{}'. {}", p.display(), e);
     }
   };
 }
 
+// copies a file into the mounted assets directory and loads it
+fn import(world: &World) {
+  let Some(p) = FileDialog::new().pick_file() else {
+    return;
+  };
+  let (Some(dir), Some(name)) = (vfs::dirs().pop(), p.file_name()) else {
+    error!("Couldnt import '{}', no assets directory.", p.display());
+    return;
+  };
+  let name = name.to_string_lossy().to_string();
+  let assets = world.get_resource::<Assets>().unwrap();
+  match fs::copy(&p, dir.join(&name))
+    .map_err(Into::into)
+    .and_then(|_| assets.load_untyped(&name))
+  {
+    Ok(handle) => world.add_resource(SelectedAsset(Some(handle))),
+    Err(e) => error!("Couldnt import '{}'. {}", p.display(), e),
+  }
+}
+
+fn toggle_play(world: &mut World) {
+  let play = world.get_resource::<PlayState>().unwrap();
+  match play.0.take() {
+    Some(scene) => {
+      world.restore(&scene);
+      world.add_resource(SelectedEntity(None));
+    }
+    None => play.0 = Some(world.snapshot()),
+  }
+}
+
 fn shortcut(s: &str) -> String {
   if cfg!(target_os = "macos") {
     "\u{e14f} "
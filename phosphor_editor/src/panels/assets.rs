@@ -1,22 +1,41 @@
 use std::any::Any;
 use std::collections::HashMap;
 use phosphor::TypeIdNamed;
-use phosphor::gfx::{Texture, Mesh, Shader, Framebuffer, Renderer, gl};
+use phosphor::gfx::{
+  Texture, Mesh, Shader, Framebuffer, Renderer, TextureSettings, MeshSettings, Filter, Wrap, Axis,
+  UniformBuffer, gl,
+};
 use phosphor::ecs::World;
-use phosphor::assets::{Assets, Handle};
+use phosphor::assets::{self, Assets, Handle, LoadState};
 use phosphor::math::{Mat4, Vec3, Quat};
-use phosphor_imgui::imgui::{Ui, WindowFlags, Image, TextureId};
+use phosphor_3d::CameraBlock;
+use phosphor_3d::gltf::Gltf;
+use phosphor_imgui::imgui::{Ui, WindowFlags, Image, TextureId, Drag};
+use phosphor::log::error;
 use crate::panels::Panel;
 
 type Preview = fn(&Ui, &World, &Handle<dyn Any>, [f32; 2]);
 
 pub struct SelectedAsset(pub Option<(TypeIdNamed, Handle<dyn Any>)>);
 
+// import settings of the selected asset, saved to its .meta on reimport
+struct ImportSettings {
+  name: String,
+  tex: TextureSettings,
+  mesh: MeshSettings,
+}
+
+const FILTERS: [Filter; 2] = [Filter::Linear, Filter::Nearest];
+const WRAPS: [Wrap; 3] = [Wrap::Clamp, Wrap::Repeat, Wrap::Mirror];
+const AXES: [Axis; 2] = [Axis::Y, Axis::Z];
+
 struct MeshPreviewState {
   fb: Framebuffer,
   textures: HashMap<String, Texture>,
   selected_tex: Texture,
-  shader: Shader,
+  shader: Handle<Shader>,
+  // bound in place of the scene camera while drawing the preview
+  camera: UniformBuffer<CameraBlock>,
   spin: f32,
 }
 
@@ -26,12 +45,22 @@ pub fn init(world: &mut World) -> Panel {
   previews.insert(TypeIdNamed::of::<Mesh>(), preview_mesh);
   world.add_resource(previews);
   world.add_resource(SelectedAsset(None));
+  world.add_resource(ImportSettings {
+    name: String::new(),
+    tex: TextureSettings::default(),
+    mesh: MeshSettings::default(),
+  });
   let fb = Framebuffer::new();
   world.add_resource(MeshPreviewState {
     fb,
     textures: HashMap::new(),
     selected_tex: Texture::empty(),
-    shader: Shader::new("base.vert", "unlit.frag").unwrap(),
+    shader: world
+      .get_resource::<Assets>()
+      .unwrap()
+      .load("shaders/unlit.shader")
+      .unwrap(),
+    camera: UniformBuffer::new("Camera"),
     spin: 0.0,
   });
   Panel {
@@ -44,13 +73,25 @@ pub fn init(world: &mut World) -> Panel {
 }
 
 fn preview_texture(ui: &Ui, _: &World, handle: &Handle<dyn Any>, size: [f32; 2]) {
-  let tex = handle.downcast::<Texture>();
+  let Some(tex) = handle.downcast::<Texture>().and_then(|h| h.get()) else {
+    return;
+  };
   let short = size[0].min(size[1]);
   Image::new(TextureId::new(tex.id as _), [short, short]).build(ui);
   corner_info(ui, size, format!("{}x{}", tex.width, tex.height));
 }
 
 fn preview_mesh(ui: &Ui, world: &World, handle: &Handle<dyn Any>, size: [f32; 2]) {
+  let (Some(mesh), Some(shader)) = (
+    handle.downcast::<Mesh>().and_then(|h| h.get()),
+    world
+      .get_resource::<MeshPreviewState>()
+      .unwrap()
+      .shader
+      .get(),
+  ) else {
+    return;
+  };
   let state = world.get_resource::<MeshPreviewState>().unwrap();
   let renderer = world.get_resource::<Renderer>().unwrap();
   let fb_size = [size[0] * 2.5, size[1] * 2.5];
@@ -72,26 +113,23 @@ fn preview_mesh(ui: &Ui, world: &World, handle: &Handle<dyn Any>, size: [f32; 2]
   state.fb.bind_tex(tex, 0);
   renderer.resize(fb_size[0] as _, fb_size[1] as _);
   renderer.clear(0.0, 0.0, 0.0, 0.0);
-  state.shader.bind();
-  state.shader.set_mat4(
+  state.camera.bind();
+  state.camera.update(&CameraBlock {
+    view: Mat4::look_at_rh(Vec3::splat(5.0), Vec3::ZERO, Vec3::Y),
+    projection: Mat4::perspective_rh(1.0, size[0] / size[1], 0.1, 50.0),
+    cam_pos: [5.0, 5.0, 5.0, 1.0],
+  });
+  shader.bind();
+  shader.set_mat4(
     "model",
     &Mat4::from_rotation_translation(Quat::from_rotation_y(spin), Vec3::NEG_Y),
   );
-  state.shader.set_mat4(
-    "view",
-    &Mat4::look_at_rh(Vec3::splat(5.0), Vec3::ZERO, Vec3::Y),
-  );
-  state.shader.set_mat4(
-    "projection",
-    &Mat4::perspective_rh(1.0, size[0] / size[1], 0.1, 50.0),
-  );
-  state.shader.set_vec3("color", &Vec3::splat(0.5));
-  let mesh = handle.downcast::<Mesh>();
+  shader.set_vec3("color", &Vec3::splat(0.5));
   mesh.draw();
   unsafe {
     gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
-    state.shader.set_vec3("color", &Vec3::ZERO);
-    handle.downcast::<Mesh>().draw();
+    shader.set_vec3("color", &Vec3::ZERO);
+    mesh.draw();
     gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
   }
   Image::new(TextureId::new(tex.id as _), size)
@@ -107,6 +145,7 @@ fn render(world: &mut World, ui: &Ui) {
     .get_resource::<HashMap<TypeIdNamed, Preview>>()
     .unwrap();
   let selected = world.get_resource::<SelectedAsset>().unwrap();
+  let settings = world.get_resource::<ImportSettings>().unwrap();
   for (t, v) in assets.handles.iter() {
     let mut pos = ui.cursor_pos();
     for handle in v {
@@ -130,7 +169,10 @@ fn render(world: &mut World, ui: &Ui) {
         ui.text(handle.name.clone());
       }
       ui.set_cursor_pos([pos[0] + 18.0, pos[1] + 8.0]);
-      match previews.get(t) {
+      match previews
+        .get(t)
+        .filter(|_| handle.state() == LoadState::Loaded)
+      {
         Some(p) => (p)(ui, world, handle, [64.0, 64.0]),
         None => {
           let font = ui.push_font(ui.fonts().fonts()[1]);
@@ -145,6 +187,15 @@ fn render(world: &mut World, ui: &Ui) {
       id.pop();
     }
   }
+  // drop previews of meshes that are no longer loaded
+  let state = world.get_resource::<MeshPreviewState>().unwrap();
+  let meshes = assets.handles.get(&TypeIdNamed::of::<Mesh>());
+  state.textures.retain(|name, _| {
+    meshes.map_or(false, |v| {
+      v.iter()
+        .any(|h| h.name == *name && h.state() == LoadState::Loaded)
+    })
+  });
   let [w, h] = ui.window_size();
   ui.set_cursor_pos([w - 320.0, 24.0]);
   ui.child_window("##")
@@ -161,18 +212,105 @@ fn render(world: &mut World, ui: &Ui) {
         ui.text_disabled(handle.0.name);
         ui.set_cursor_pos([8.0, pos[1] + 54.0]);
         ui.separator();
-        match previews.get(&handle.0) {
-          Some(p) => {
+        if import_settings(ui, handle, settings) {
+          assets.reload(&handle.1.name);
+        }
+        // gltf scenes are flattened into entities
+        if handle.0 == TypeIdNamed::of::<Gltf>()
+          && handle.1.state() == LoadState::Loaded
+          && ui.button("\u{f1b2} Spawn")
+        {
+          if let Some(gltf) = handle.1.downcast::<Gltf>().and_then(|h| h.get()) {
+            gltf.spawn(world, Mat4::IDENTITY);
+          }
+        }
+        let [_, y] = ui.cursor_pos();
+        match (previews.get(&handle.0), handle.1.state()) {
+          (_, LoadState::Loading) => ui.text("\u{f110} Loading..."),
+          (_, LoadState::Failed) => ui.text("\u{f071} Failed to load."),
+          (_, LoadState::Unloaded) => ui.text("\u{f071} Unloaded."),
+          (Some(p), LoadState::Loaded) => {
             ui.text("Preview:");
-            (p)(ui, world, &handle.1, [296.0, h - 128.0]);
+            (p)(ui, world, &handle.1, [296.0, h - y - 40.0]);
           }
-          None => ui.text("\u{f071} No preview available."),
+          (None, LoadState::Loaded) => ui.text("\u{f071} No preview available."),
         }
       }
       None => ui.text("\u{f071} No asset selected."),
     });
 }
 
+// true when the asset should be reimported
+fn import_settings(
+  ui: &Ui,
+  (t, handle): &(TypeIdNamed, Handle<dyn Any>),
+  settings: &mut ImportSettings,
+) -> bool {
+  let is_tex = *t == TypeIdNamed::of::<Texture>();
+  if !is_tex && *t != TypeIdNamed::of::<Mesh>() {
+    return false;
+  }
+  if settings.name != handle.name {
+    settings.name = handle.name.clone();
+    settings.tex = assets::load_settings(&handle.name).unwrap_or_default();
+    settings.mesh = assets::load_settings(&handle.name).unwrap_or_default();
+  }
+  ui.text("Import Settings:");
+  if is_tex {
+    let tex = &mut settings.tex;
+    ui.checkbox("sRGB", &mut tex.srgb);
+    enum_combo(
+      ui,
+      "Filter",
+      &mut tex.filter,
+      &FILTERS,
+      &["Linear", "Nearest"],
+    );
+    enum_combo(
+      ui,
+      "Wrap",
+      &mut tex.wrap,
+      &WRAPS,
+      &["Clamp", "Repeat", "Mirror"],
+    );
+    ui.checkbox("Mipmaps", &mut tex.mipmaps);
+    Drag::new("Anisotropy")
+      .speed(0.1)
+      .range(1.0, 16.0)
+      .build(ui, &mut tex.anisotropy);
+    ui.checkbox("Flip", &mut tex.flip);
+  } else {
+    let mesh = &mut settings.mesh;
+    Drag::new("Scale")
+      .speed(0.01)
+      .range(0.001, f32::MAX)
+      .build(ui, &mut mesh.scale);
+    enum_combo(ui, "Up", &mut mesh.up, &AXES, &["Y", "Z"]);
+  }
+  if !ui.button("\u{f2f9} Reimport") {
+    return false;
+  }
+  let res = if is_tex {
+    assets::save_settings(&handle.name, &settings.tex)
+  } else {
+    assets::save_settings(&handle.name, &settings.mesh)
+  };
+  match res {
+    Ok(_) => true,
+    Err(e) => {
+      error!("Couldnt save settings for '{}'. {}", handle.name, e);
+      false
+    }
+  }
+}
+
+fn enum_combo<T: Copy + PartialEq>(ui: &Ui, label: &str, val: &mut T, vals: &[T], names: &[&str]) {
+  let mut i = vals.iter().position(|v| v == val).unwrap_or(0);
+  if ui.combo_simple_string(label, &mut i, names) {
+    *val = vals[i];
+  }
+}
+
 fn corner_info(ui: &Ui, size: [f32; 2], info: String) {
   if size[0] != size[1] {
     let [w, h] = ui.content_region_max();
@@ -158,7 +158,7 @@ fn inspector_model(t: &mut Box<dyn Any>, ui: &Ui, world: &mut World) {
 
 fn model_default(world: &mut World) -> Box<dyn Any> {
   let assets = world.get_resource::<Assets>().unwrap();
-  Box::new(Model::new(assets.load("cube.obj").unwrap()))
+  Box::new(Model::new(assets.load("builtin:cube").unwrap()))
 }
 
 fn inspector_material(t: &mut Box<dyn Any>, ui: &Ui, world: &mut World) {
@@ -279,7 +279,9 @@ fn asset_picker<T: Any>(ui: &Ui, label: &str, world: &mut World, handle: &mut Ha
     if let Some(_) = target.accept_payload_empty(std::any::type_name::<T>(), DragDropFlags::empty())
     {
       let selected = world.get_resource::<SelectedAsset>().unwrap();
-      *handle = selected.0.as_ref().unwrap().1.downcast();
+      if let Some(h) = selected.0.as_ref().unwrap().1.downcast() {
+        *handle = h;
+      }
     }
   }
   id.pop();
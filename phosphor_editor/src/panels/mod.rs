@@ -10,6 +10,8 @@ use phosphor::Result;
 use phosphor::ecs::World;
 use phosphor_imgui::imgui::{Ui, WindowFlags, StyleVar};
 
+pub use assets::SelectedAsset;
+
 pub struct Panel {
   pub title: &'static str,
   pub flags: WindowFlags,
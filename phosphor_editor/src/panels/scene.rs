@@ -1,4 +1,5 @@
 use std::f32::consts::FRAC_PI_2;
+use std::rc::Rc;
 use phosphor::Result;
 use phosphor::ecs::{World, Name, stage};
 use phosphor::gfx::{Texture, Framebuffer, Renderer};
@@ -13,7 +14,8 @@ struct SceneState {
   size: [f32; 2],
   focused: bool,
   cam: bool,
-  fb: Framebuffer,
+  // shared with the scene renderer
+  fb: Rc<Framebuffer>,
   tex: Texture,
   last_pos: (f32, f32),
 }
@@ -27,7 +29,7 @@ pub fn init(world: &mut World) -> Result<Panel> {
     size: [0.0, 0.0],
     focused: false,
     cam: false,
-    fb,
+    fb: Rc::new(fb),
     tex,
     last_pos: (0.0, 0.0),
   });
@@ -94,7 +96,7 @@ fn predraw(world: &mut World) -> Result {
     renderer.window.set_cursor_mode(CursorMode::Normal);
   }
   world.add_resource(SceneDrawOptions {
-    fb: s.fb,
+    fb: s.fb.clone(),
     size: [s.size[0] * 2.5, s.size[1] * 2.5],
   });
   Ok(())
@@ -1,6 +1,7 @@
 use std::env::consts;
 use phosphor::ecs::World;
 use phosphor::gfx::Renderer;
+use phosphor::assets::Assets;
 use phosphor_imgui::imgui::{Context, Ui, WindowFlags, StyleVar, dear_imgui_version};
 use phosphor_fmod::FmodContext;
 use crate::panels::Panel;
@@ -8,15 +9,17 @@ use crate::panels::Panel;
 #[derive(PartialEq, Eq)]
 enum SettingsPane {
   Appearance,
+  Memory,
   About,
 }
 
 impl SettingsPane {
-  const ALL: [Self; 2] = [Self::Appearance, Self::About];
+  const ALL: [Self; 3] = [Self::Appearance, Self::Memory, Self::About];
 
   fn name(&self) -> &str {
     match self {
       Self::Appearance => "\u{f53f} Appearance",
+      Self::Memory => "\u{f538} Memory",
       Self::About => "\u{f05a} About",
     }
   }
@@ -68,6 +71,21 @@ fn render(world: &mut World, ui: &Ui) {
           }
         }
       },
+      SettingsPane::Memory => {
+        let assets = world.get_resource::<Assets>().unwrap();
+        for r in assets.memory_report() {
+          item(
+            ui,
+            r.id.name,
+            &format!(
+              "{} loaded, {} cpu, {} gpu",
+              r.count,
+              bytes(r.memory.cpu),
+              bytes(r.memory.gpu)
+            ),
+          );
+        }
+      }
       SettingsPane::About => {
         let font = ui.push_font(ui.fonts().fonts()[1]);
         ui.text("\u{f5d3} Phosphor");
@@ -108,3 +126,11 @@ fn item(ui: &Ui, label: &str, text: &str) {
   ui.same_line_with_pos(w - x);
   ui.text_disabled(text);
 }
+
+fn bytes(b: usize) -> String {
+  match b {
+    0..=1023 => format!("{} B", b),
+    1024..=1048575 => format!("{:.1} KiB", b as f32 / 1024.0),
+    _ => format!("{:.1} MiB", b as f32 / 1048576.0),
+  }
+}
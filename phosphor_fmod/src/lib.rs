@@ -1,13 +1,14 @@
-use std::ptr;
+use std::{ptr, mem};
 use std::ffi::CString;
 use libfmod::{System, Sound as FmodSound, Channel};
 use libfmod::ffi::{
-  FMOD_INIT_3D_RIGHTHANDED, FMOD_3D, FMOD_VECTOR, FMOD_System_GetDriverInfo,
-  FMOD_System_Set3DListenerAttributes, FMOD_Channel_Set3DAttributes,
+  FMOD_INIT_3D_RIGHTHANDED, FMOD_3D, FMOD_OPENMEMORY, FMOD_OK, FMOD_VECTOR, FMOD_CREATESOUNDEXINFO,
+  FMOD_System_GetDriverInfo, FMOD_System_CreateSound, FMOD_System_Set3DListenerAttributes,
+  FMOD_Channel_Set3DAttributes,
 };
 use phosphor::assets::Handle;
 use phosphor::ecs::{World, stage};
-use phosphor::{Result, asset, component};
+use phosphor::{Result, asset, component, vfs};
 use phosphor::log::debug;
 use phosphor::math::Vec3;
 use phosphor_3d::{Camera, Transform};
@@ -101,18 +102,31 @@ fn fmod_predraw(world: &mut World) -> Result {
   Ok(())
 }
 
-#[asset(load_sound)]
+// not cooked, fmod decodes every one of these formats itself and decoding them ahead of time
+// would only make them bigger
+#[asset(load_sound, ext = ["wav", "mp3", "ogg", "flac"])]
 pub struct Sound(pub FmodSound);
 
 fn load_sound(world: &mut World, path: &str) -> Result<Sound> {
-  Ok(Sound(
-    world
-      .get_resource::<FmodContext>()
-      .unwrap()
-      .system
-      .create_sound(path, FMOD_3D, None)
-      .unwrap(),
-  ))
+  let data = vfs::read(path)?;
+  let system = &world.get_resource::<FmodContext>().unwrap().system;
+  unsafe {
+    // fmod copies the data with OPENMEMORY
+    let mut info: FMOD_CREATESOUNDEXINFO = mem::zeroed();
+    info.cbsize = mem::size_of::<FMOD_CREATESOUNDEXINFO>() as _;
+    info.length = data.len() as _;
+    let mut sound = ptr::null_mut();
+    match FMOD_System_CreateSound(
+      system.as_mut_ptr(),
+      data.as_ptr() as _,
+      FMOD_3D | FMOD_OPENMEMORY,
+      &mut info,
+      &mut sound,
+    ) {
+      FMOD_OK => Ok(Sound(FmodSound::from(sound))),
+      e => Err(format!("FMOD error {}.", e).into()),
+    }
+  }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -135,12 +149,16 @@ impl AudioSource {
     }
   }
 
+  // does nothing while the sound is loading
   pub fn play(&mut self, world: &World) {
+    let Some(sound) = self.sound.get() else {
+      return;
+    };
     let channel = world
       .get_resource::<FmodContext>()
       .unwrap()
       .system
-      .play_sound(self.sound.0, None, false)
+      .play_sound(sound.0, None, false)
       .unwrap();
     channel.set_pitch(self.pitch).unwrap();
     self.channel = Some(channel);
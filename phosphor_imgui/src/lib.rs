@@ -1,4 +1,3 @@
-use std::fs;
 use std::time::Instant;
 use imgui::{
   Context, Ui, Style, StyleColor, ConfigFlags, MouseCursor, BackendFlags, Key, FontConfig,
@@ -7,9 +6,10 @@ use imgui::{
 use phosphor::glfw::{
   Cursor, StandardCursor, CursorMode, WindowEvent, Action, Modifiers, MouseButton, Key as GlfwKey,
 };
-use phosphor::Result;
+use phosphor::{Result, vfs};
 use phosphor::gfx::{Renderer, Shader, Texture, gl};
 use phosphor::ecs::{World, stage};
+use phosphor::assets::{Assets, Handle};
 use phosphor::math::Mat4;
 use phosphor::log::debug;
 
@@ -23,12 +23,13 @@ pub struct UiRendererOptions {
 impl UiRendererOptions {
   const DEFAULT: Self = Self {
     docking: false,
-    fonts: &[&[("assets/fonts/roboto.ttf", 16.0, None)]],
+    fonts: &[&[("fonts/roboto.ttf", 16.0, None)]],
   };
 }
 
 struct UiRenderer {
-  shader: Shader,
+  shader: Handle<Shader>,
+  _font_tex: Texture,
   vert_arr: u32,
   vert_buf: u32,
   idx_buf: u32,
@@ -83,7 +84,7 @@ pub fn imgui_plugin(world: &mut World) -> Result {
       &font
         .iter()
         .map(|f| imgui::FontSource::TtfData {
-          data: Box::leak(fs::read(f.0).unwrap().into_boxed_slice()),
+          data: Box::leak(vfs::read(f.0).unwrap().into_boxed_slice()),
           size_pixels: f.1,
           config: f.2.map(|g| FontConfig {
             glyph_ranges: FontGlyphRanges::from_slice(g),
@@ -93,25 +94,26 @@ pub fn imgui_plugin(world: &mut World) -> Result {
         .collect::<Vec<_>>(),
     );
   }
-  let font_tex = fonts.build_rgba32_texture();
-  fonts.tex_id = TextureId::new(
-    Texture::new(
-      font_tex.data.as_ptr(),
-      font_tex.width,
-      font_tex.height,
-      gl::SRGB_ALPHA,
-      gl::RGBA,
-      gl::UNSIGNED_BYTE,
-    )
-    .id as _,
+  let font_atlas = fonts.build_rgba32_texture();
+  let font_tex = Texture::new(
+    font_atlas.data.as_ptr(),
+    font_atlas.width,
+    font_atlas.height,
+    gl::SRGB_ALPHA,
+    gl::RGBA,
+    gl::UNSIGNED_BYTE,
   );
+  fonts.tex_id = TextureId::new(font_tex.id as _);
   let style = ctx.style_mut();
   theme_dark(style);
   style.window_rounding = 4.0;
   style.popup_rounding = 4.0;
   style.frame_rounding = 2.0;
 
-  let shader = Shader::new("imgui.vert", "imgui.frag")?;
+  let shader = world
+    .get_resource::<Assets>()
+    .unwrap()
+    .load("shaders/imgui.shader")?;
   let mut vert_arr = 0;
   let mut vert_buf = 0;
   let mut idx_buf = 0;
@@ -138,6 +140,7 @@ pub fn imgui_plugin(world: &mut World) -> Result {
   world.add_resource(ctx);
   world.add_resource(UiRenderer {
     shader,
+    _font_tex: font_tex,
     vert_arr,
     vert_buf,
     idx_buf,
@@ -255,8 +258,9 @@ fn imgui_draw(world: &mut World) -> Result {
       }
 
       let [w, h] = ui.io().display_size;
-      r.shader.bind();
-      r.shader.set_mat4(
+      let shader = r.shader.loaded()?;
+      shader.bind();
+      shader.set_mat4(
         "transform",
         &Mat4::orthographic_rh(0.0, w as _, h as _, 0.0, 0.0, 1.0),
       );
@@ -1,7 +1,10 @@
 #![feature(vec_into_raw_parts)]
-use phosphor::{Result, DeltaTime, component};
+use std::any::Any;
+use std::collections::HashSet;
+use phosphor::{Result, DeltaTime, TypeIdNamed, resource, bincode};
 use phosphor::ecs::{World, stage};
-use phosphor::math::{Vec3, Mat4};
+use phosphor::scene::{Loader, COMPONENT_LOADERS, to_json, from_json};
+use phosphor::math::{Vec3, Quat, Mat4};
 use phosphor::log::debug;
 use phosphor::gfx::Mesh;
 use phosphor_3d::{Transform, Camera};
@@ -10,11 +13,36 @@ use rapier3d::prelude::*;
 use rapier3d::dynamics::{RigidBody as RapierRigidBody, RigidBodyBuilder as RapierRigidBodyBuilder};
 use rapier3d::geometry::{Collider as RapierCollider, ColliderBuilder as RapierColliderBuilder};
 use serde::{Serialize, Deserialize};
+use linkme::distributed_slice;
 
 pub use rapier3d;
 
+#[derive(Serialize, Deserialize)]
+#[resource]
 pub struct Gravity(pub Vec3);
 
+// bodies and colliders made for RigidBody and Collider components, removed along with them
+// anything else in the sets belongs to whoever put it there
+#[derive(Default)]
+struct Owned {
+  bodies: HashSet<RigidBodyHandle>,
+  colliders: HashSet<ColliderHandle>,
+}
+
+fn own_body(world: &World, handle: RigidBodyHandle) -> RigidBody {
+  world.get_resource::<Owned>().unwrap().bodies.insert(handle);
+  RigidBody { handle }
+}
+
+fn own_collider(world: &World, handle: ColliderHandle, attach: bool) -> Collider {
+  world
+    .get_resource::<Owned>()
+    .unwrap()
+    .colliders
+    .insert(handle);
+  Collider { handle, attach }
+}
+
 pub struct RigidBodyBuilder {
   b: RapierRigidBodyBuilder,
 }
@@ -32,29 +60,124 @@ impl RigidBodyBuilder {
     }
   }
 
+  // on top of the mass of its colliders
+  pub fn additional_mass(mut self, mass: f32) -> Self {
+    self.b = self.b.additional_mass(mass);
+    self
+  }
+
   pub fn build(self, world: &World) -> RigidBody {
-    RigidBody {
-      handle: world
-        .get_resource::<RigidBodySet>()
-        .unwrap()
-        .insert(self.b.build()),
-    }
+    let handle = world
+      .get_resource::<RigidBodySet>()
+      .unwrap()
+      .insert(self.b.build());
+    own_body(world, handle)
   }
 }
 
-#[derive(Copy, Clone, Serialize, Deserialize)]
-#[component]
+#[derive(Copy, Clone)]
 pub struct RigidBody {
   pub handle: RigidBodyHandle,
 }
 
+#[derive(Serialize, Deserialize)]
+struct RigidBodyData {
+  body_type: RigidBodyType,
+  translation: Vec3,
+  rotation: Quat,
+  linvel: Vec3,
+  angvel: Vec3,
+  gravity_scale: f32,
+  linear_damping: f32,
+  angular_damping: f32,
+  dominance_group: i8,
+  locked_axes: LockedAxes,
+  ccd: bool,
+  additional_mass: Option<RigidBodyAdditionalMassProps>,
+}
+
+#[distributed_slice(COMPONENT_LOADERS)]
+static RIGIDBODY_LOADER: Loader = Loader {
+  id: TypeIdNamed::of::<RigidBody>(),
+  save: save_rigidbody,
+  load: load_rigidbody,
+  restore: Some(restore_rigidbody),
+  to_json: to_json::<RigidBodyData>,
+  from_json: from_json::<RigidBodyData>,
+};
+
+fn save_rigidbody(data: &Box<dyn Any>, world: &World) -> Result<Vec<u8>> {
+  let rb = data
+    .downcast_ref::<RigidBody>()
+    .unwrap()
+    .try_get(world)
+    .ok_or("Rigidbody isnt in the physics world.")?;
+  Ok(bincode::serialize(&RigidBodyData {
+    body_type: rb.body_type(),
+    translation: (*rb.translation()).into(),
+    rotation: (*rb.rotation()).into(),
+    linvel: (*rb.linvel()).into(),
+    angvel: (*rb.angvel()).into(),
+    gravity_scale: rb.gravity_scale(),
+    linear_damping: rb.linear_damping(),
+    angular_damping: rb.angular_damping(),
+    dominance_group: rb.dominance_group(),
+    locked_axes: rb.locked_axes(),
+    ccd: rb.is_ccd_enabled(),
+    additional_mass: rb
+      .mass_properties()
+      .additional_local_mprops
+      .as_deref()
+      .copied(),
+  })?)
+}
+
+fn load_rigidbody(data: &[u8], world: &World) -> Result<Box<dyn Any>> {
+  let d: RigidBodyData = bincode::deserialize(data)?;
+  let mut rb = RapierRigidBodyBuilder::new(d.body_type).build();
+  apply_rigidbody(&mut rb, d);
+  let handle = world.get_resource::<RigidBodySet>().unwrap().insert(rb);
+  Ok(Box::new(own_body(world, handle)))
+}
+
+// keeps the body in the physics world instead of making a new one
+fn restore_rigidbody(c: &mut Box<dyn Any>, data: &[u8], world: &World) -> Result {
+  let rb = c
+    .downcast_ref::<RigidBody>()
+    .unwrap()
+    .try_get(world)
+    .ok_or("Rigidbody isnt in the physics world.")?;
+  apply_rigidbody(rb, bincode::deserialize(data)?);
+  Ok(())
+}
+
+fn apply_rigidbody(rb: &mut RapierRigidBody, d: RigidBodyData) {
+  rb.set_body_type(d.body_type, true);
+  rb.set_translation(d.translation.into(), true);
+  rb.set_rotation(d.rotation.into(), true);
+  rb.set_linvel(d.linvel.into(), true);
+  rb.set_angvel(d.angvel.into(), true);
+  rb.set_gravity_scale(d.gravity_scale, true);
+  rb.set_linear_damping(d.linear_damping);
+  rb.set_angular_damping(d.angular_damping);
+  rb.set_dominance_group(d.dominance_group);
+  rb.set_locked_axes(d.locked_axes, true);
+  rb.enable_ccd(d.ccd);
+  match d.additional_mass {
+    Some(RigidBodyAdditionalMassProps::Mass(m)) => rb.set_additional_mass(m, true),
+    Some(RigidBodyAdditionalMassProps::MassProps(p)) => rb.set_additional_mass_properties(p, true),
+    None => rb.set_additional_mass(0.0, true),
+  }
+}
+
 impl RigidBody {
   pub fn get<'s>(&'s self, world: &'s World) -> &mut RapierRigidBody {
-    world
-      .get_resource::<RigidBodySet>()
-      .unwrap()
-      .get_mut(self.handle)
-      .unwrap()
+    self.try_get(world).unwrap()
+  }
+
+  // None if the body was removed from the physics world
+  pub fn try_get<'s>(&'s self, world: &'s World) -> Option<&mut RapierRigidBody> {
+    world.get_resource::<RigidBodySet>()?.get_mut(self.handle)
   }
 }
 
@@ -89,9 +212,9 @@ impl ColliderBuilder {
     Self {
       b: RapierColliderBuilder::convex_hull(
         mesh
-          .vertices
+          .positions()
           .iter()
-          .map(|v| v.pos.into())
+          .map(|p| p.to_array().into())
           .collect::<Vec<_>>()
           .as_slice(),
       )
@@ -105,9 +228,9 @@ impl ColliderBuilder {
     Self {
       b: RapierColliderBuilder::trimesh(
         mesh
-          .vertices
+          .positions()
           .iter()
-          .map(|v| v.pos.into())
+          .map(|p| p.to_array().into())
           .collect::<Vec<_>>(),
         unsafe { Vec::from_raw_parts(v.0 as _, v.1 / 3, v.2 / 3) },
       ),
@@ -127,32 +250,135 @@ impl ColliderBuilder {
 
   pub fn build(self, world: &World) -> Collider {
     let set = world.get_resource::<ColliderSet>().unwrap();
-    Collider {
-      handle: match self.rb {
-        Some(rb) => set.insert_with_parent(
-          self.b.build(),
-          rb.handle,
-          world.get_resource::<RigidBodySet>().unwrap(),
-        ),
-        None => set.insert(self.b.build()),
-      },
-    }
+    let handle = match self.rb {
+      Some(rb) => set.insert_with_parent(
+        self.b.build(),
+        rb.handle,
+        world.get_resource::<RigidBodySet>().unwrap(),
+      ),
+      None => set.insert(self.b.build()),
+    };
+    own_collider(world, handle, false)
   }
 }
 
-#[derive(Serialize, Deserialize)]
-#[component]
 pub struct Collider {
   pub handle: ColliderHandle,
+  // loaded colliders are attached to their entity's rigidbody on the next update
+  attach: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ColliderData {
+  shape: SharedShape,
+  translation: Vec3,
+  rotation: Quat,
+  mass_props: ColliderMassProps,
+  friction: f32,
+  friction_combine_rule: CoefficientCombineRule,
+  restitution: f32,
+  restitution_combine_rule: CoefficientCombineRule,
+  collision_groups: InteractionGroups,
+  solver_groups: InteractionGroups,
+  sensor: bool,
+  attached: bool,
+}
+
+#[distributed_slice(COMPONENT_LOADERS)]
+static COLLIDER_LOADER: Loader = Loader {
+  id: TypeIdNamed::of::<Collider>(),
+  save: save_collider,
+  load: load_collider,
+  restore: Some(restore_collider),
+  to_json: to_json::<ColliderData>,
+  from_json: from_json::<ColliderData>,
+};
+
+fn save_collider(data: &Box<dyn Any>, world: &World) -> Result<Vec<u8>> {
+  let collider = data.downcast_ref::<Collider>().unwrap();
+  let c = collider
+    .try_get(world)
+    .ok_or("Collider isnt in the physics world.")?;
+  let pos = c.position_wrt_parent().unwrap_or(c.position());
+  Ok(bincode::serialize(&ColliderData {
+    shape: c.shared_shape().clone(),
+    translation: pos.translation.vector.into(),
+    rotation: pos.rotation.into(),
+    mass_props: mass_props(c)?,
+    friction: c.friction(),
+    friction_combine_rule: c.friction_combine_rule(),
+    restitution: c.restitution(),
+    restitution_combine_rule: c.restitution_combine_rule(),
+    collision_groups: c.collision_groups(),
+    solver_groups: c.solver_groups(),
+    sensor: c.is_sensor(),
+    // loaded colliders arent attached until the next update
+    attached: c.parent().is_some() || collider.attach,
+  })?)
+}
+
+fn load_collider(data: &[u8], world: &World) -> Result<Box<dyn Any>> {
+  let d: ColliderData = bincode::deserialize(data)?;
+  let attach = d.attached;
+  let mut c = RapierColliderBuilder::new(d.shape.clone()).build();
+  apply_collider(&mut c, d);
+  let handle = world.get_resource::<ColliderSet>().unwrap().insert(c);
+  Ok(Box::new(own_collider(world, handle, attach)))
+}
+
+// keeps the collider in the physics world instead of making a new one
+fn restore_collider(c: &mut Box<dyn Any>, data: &[u8], world: &World) -> Result {
+  let d: ColliderData = bincode::deserialize(data)?;
+  let collider = c.downcast_mut::<Collider>().unwrap();
+  let c = collider
+    .try_get(world)
+    .ok_or("Collider isnt in the physics world.")?;
+  let attach = d.attached && c.parent().is_none();
+  apply_collider(c, d);
+  collider.attach = attach;
+  Ok(())
+}
+
+// attached colliders are positioned relative to their body
+fn apply_collider(c: &mut RapierCollider, d: ColliderData) {
+  let pos = Isometry::from_parts(d.translation.into(), d.rotation.into());
+  if c.parent().is_some() {
+    c.set_position_wrt_parent(pos);
+  } else {
+    c.set_position(pos);
+  }
+  c.set_shape(d.shape);
+  match d.mass_props {
+    ColliderMassProps::Density(density) => c.set_density(density),
+    ColliderMassProps::Mass(mass) => c.set_mass(mass),
+    ColliderMassProps::MassProperties(p) => c.set_mass_properties(*p),
+  }
+  c.set_friction(d.friction);
+  c.set_friction_combine_rule(d.friction_combine_rule);
+  c.set_restitution(d.restitution);
+  c.set_restitution_combine_rule(d.restitution_combine_rule);
+  c.set_collision_groups(d.collision_groups);
+  c.set_solver_groups(d.solver_groups);
+  c.set_sensor(d.sensor);
+}
+
+// rapier has no getter for how the mass was given (density, mass or mass properties),
+// so it is read back through serde from a copy with an empty shape
+fn mass_props(c: &RapierCollider) -> Result<ColliderMassProps> {
+  let mut c = c.clone();
+  c.set_shape(SharedShape::ball(0.0));
+  let mut value = serde_json::to_value(&c)?;
+  Ok(serde_json::from_value(value["mprops"].take())?)
 }
 
 impl Collider {
   pub fn get<'s>(&'s self, world: &'s World) -> &mut RapierCollider {
-    world
-      .get_resource::<ColliderSet>()
-      .unwrap()
-      .get_mut(self.handle)
-      .unwrap()
+    self.try_get(world).unwrap()
+  }
+
+  // None if the collider was removed from the physics world
+  pub fn try_get<'s>(&'s self, world: &'s World) -> Option<&mut RapierCollider> {
+    world.get_resource::<ColliderSet>()?.get_mut(self.handle)
   }
 }
 
@@ -167,6 +393,7 @@ pub fn rapier_plugin(world: &mut World) -> Result {
   world.add_resource(ImpulseJointSet::new());
   world.add_resource(MultibodyJointSet::new());
   world.add_resource(CCDSolver::new());
+  world.add_resource(Owned::default());
   world.add_system(stage::PRE_DRAW, rapier_update);
   debug!("Initialized Rapier {}.", rapier3d::VERSION);
   Ok(())
@@ -191,6 +418,45 @@ fn rapier_update(world: &mut World) -> Result {
   let impulse_joint_set = world.get_resource::<ImpulseJointSet>().unwrap();
   let multibody_joint_set = world.get_resource::<MultibodyJointSet>().unwrap();
   let ccd_solver = world.get_resource::<CCDSolver>().unwrap();
+  let owned = world.get_resource::<Owned>().unwrap();
+  // remove bodies and colliders whose components are gone, eg. after loading a scene
+  let live: HashSet<_> = world
+    .query::<RigidBody>()
+    .iter()
+    .map(|(_, rb)| rb.handle)
+    .collect();
+  owned.bodies.retain(|h| {
+    if !live.contains(h) {
+      // colliders of the body are kept, they are removed with their own component
+      rb_set.remove(
+        *h,
+        island_manager,
+        collider_set,
+        impulse_joint_set,
+        multibody_joint_set,
+        false,
+      );
+    }
+    live.contains(h)
+  });
+  let colliders = world.query::<Collider>();
+  let live: HashSet<_> = colliders.iter().map(|(_, c)| c.handle).collect();
+  owned.colliders.retain(|h| {
+    if !live.contains(h) {
+      collider_set.remove(*h, island_manager, rb_set, true);
+    }
+    live.contains(h)
+  });
+  for (e, collider) in colliders {
+    if collider.attach {
+      if let Some(rb) = e.get_one::<RigidBody>() {
+        let pos = *collider.get(world).position();
+        collider_set.set_parent(collider.handle, Some(rb.handle), rb_set);
+        collider.get(world).set_position_wrt_parent(pos);
+      }
+      collider.attach = false;
+    }
+  }
   for (e, collider) in world.query::<Collider>() {
     if e.get_one::<RigidBody>().is_none() {
       if let Some(t) = e.get_one::<Transform>() {
@@ -291,3 +557,16 @@ fn debug_update(world: &mut World) -> Result {
   );
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn mass_props_are_read_as_given() {
+    let c = RapierColliderBuilder::ball(1.0).mass(3.0).build();
+    assert!(mass_props(&c).unwrap() == ColliderMassProps::Mass(3.0));
+    let c = RapierColliderBuilder::ball(1.0).density(2.0).build();
+    assert!(mass_props(&c).unwrap() == ColliderMassProps::Density(2.0));
+  }
+}
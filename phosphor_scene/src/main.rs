@@ -0,0 +1,154 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::path::Path;
+use std::process::exit;
+use phosphor::{Engine, Result};
+use phosphor::ecs::World;
+use phosphor::assets::Assets;
+use phosphor::scene::{Scene, COMPONENT_LOADERS, RESOURCE_LOADERS};
+use phosphor_rapier::rapier_plugin;
+use serde_json::Value;
+// linked for their loaders
+use phosphor_3d as _;
+use phosphor_fmod as _;
+
+const USAGE: &str = "usage:
+  phosphor-scene validate <scene>...
+  phosphor-scene dump <scene>
+  phosphor-scene diff <a> <b>
+  phosphor-scene convert <from> <to>
+
+.json scenes are read and written as text, anything else as bincode.";
+
+fn main() {
+  let args: Vec<String> = env::args().skip(1).collect();
+  let mut engine = Engine::new()
+    .mount("assets")
+    .add_resource(Assets::headless());
+  match run(
+    engine.world(),
+    &args.iter().map(String::as_str).collect::<Vec<_>>(),
+  ) {
+    Ok(true) => {}
+    Ok(false) => exit(1),
+    Err(e) => {
+      eprintln!("error: {}", e);
+      exit(1);
+    }
+  }
+}
+
+// Ok(false) when the scene is invalid or the diff isnt empty
+fn run(world: &mut World, args: &[&str]) -> Result<bool> {
+  rapier_plugin(world)?;
+  match args {
+    ["validate", paths @ ..] if !paths.is_empty() => Ok(validate(world, paths)),
+    ["dump", path] => dump(path),
+    ["diff", a, b] => diff(a, b),
+    ["convert", from, to] => convert(from, to),
+    _ => {
+      eprintln!("{}", USAGE);
+      exit(2);
+    }
+  }
+}
+
+fn validate(world: &World, paths: &[&str]) -> bool {
+  let mut ok = true;
+  for path in paths {
+    let scene = match Scene::read(Path::new(path)) {
+      Ok(s) => s,
+      // text scenes fail with every error, one per line
+      Err(e) => {
+        for line in e.to_string().lines() {
+          println!("{}: {}", path, line);
+        }
+        ok = false;
+        continue;
+      }
+    };
+    let mut errors = vec![];
+    for (id, v) in scene.entities.iter() {
+      for (t, d) in v {
+        match COMPONENT_LOADERS.iter().find(|l| l.id.id() == *t) {
+          Some(loader) => {
+            if let Err(e) = (loader.load)(d, world) {
+              errors.push(format!("entity {}: '{}': {}", id, loader.id.name, e));
+            }
+          }
+          None => errors.push(format!("entity {}: unknown component {:016x}", id, t)),
+        }
+      }
+    }
+    for (t, d) in scene.resources.iter() {
+      match RESOURCE_LOADERS.iter().find(|l| l.id.id() == *t) {
+        Some(loader) => {
+          if let Err(e) = (loader.load)(d, world) {
+            errors.push(format!("resource '{}': {}", loader.id.name, e));
+          }
+        }
+        None => errors.push(format!("unknown resource {:016x}", t)),
+      }
+    }
+    if errors.is_empty() {
+      println!("{}: ok, {} entities", path, scene.entities.len());
+    } else {
+      ok = false;
+      for e in errors {
+        println!("{}: {}", path, e);
+      }
+    }
+  }
+  ok
+}
+
+fn dump(path: &str) -> Result<bool> {
+  let text = Scene::read(Path::new(path))?.to_text()?;
+  println!("{}", serde_json::to_string_pretty(&text)?);
+  Ok(true)
+}
+
+fn diff(a: &str, b: &str) -> Result<bool> {
+  let a = Scene::read(Path::new(a))?.to_text()?;
+  let b = Scene::read(Path::new(b))?.to_text()?;
+  let ids: BTreeSet<_> = a.entities.keys().chain(b.entities.keys()).collect();
+  for id in ids {
+    match (a.entities.get(id), b.entities.get(id)) {
+      (Some(_), None) => println!("- entity {}", id),
+      (None, Some(_)) => println!("+ entity {}", id),
+      (Some(ca), Some(cb)) if ca != cb => {
+        let names: BTreeSet<_> = ca.iter().chain(cb.iter()).map(|(n, _)| n).collect();
+        for name in names {
+          let va: Vec<_> = ca.iter().filter(|(n, _)| n == name).map(|c| &c.1).collect();
+          let vb: Vec<_> = cb.iter().filter(|(n, _)| n == name).map(|c| &c.1).collect();
+          if va != vb {
+            print_values('-', &format!("entity {} '{}'", id, name), &va);
+            print_values('+', &format!("entity {} '{}'", id, name), &vb);
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+  let names: BTreeSet<_> = a.resources.keys().chain(b.resources.keys()).collect();
+  for name in names {
+    let va: Vec<_> = a.resources.get(name).into_iter().collect();
+    let vb: Vec<_> = b.resources.get(name).into_iter().collect();
+    if va != vb {
+      print_values('-', &format!("resource '{}'", name), &va);
+      print_values('+', &format!("resource '{}'", name), &vb);
+    }
+  }
+  Ok(a == b)
+}
+
+fn print_values(sign: char, what: &str, values: &[&Value]) {
+  for v in values {
+    println!("{} {} {}", sign, what, v);
+  }
+}
+
+fn convert(from: &str, to: &str) -> Result<bool> {
+  Scene::read(Path::new(from))?.write(Path::new(to))?;
+  Ok(true)
+}